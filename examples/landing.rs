@@ -16,7 +16,8 @@ fn main() {
     window.set_position((400, 100).into());
 
     let mut device = fehui_d2d::Device::create();
-    let swapchain = fehui_d2d::Swapchain::create_from_hwnd(&device, window.get_hwnd() as *mut _);
+    let mut swapchain =
+        fehui_d2d::Swapchain::create_from_hwnd(&device, window.get_hwnd() as *mut _);
 
     let img = image::open(&Path::new("examples/bg_tokyo_small.png"))
         .unwrap()
@@ -25,32 +26,6 @@ fn main() {
     let img_height = img.height();
     let img_data = img.into_raw();
 
-    let image = device
-        .make_image(
-            img_width as _,
-            img_height as _,
-            &img_data,
-            ImageFormat::RgbaSeparate,
-        )
-        .unwrap();
-
-    let brush = device.solid_brush(piet::Color::rgb(1.0, 1.0, 1.0));
-    let gradient = device
-        .gradient(piet::Gradient::Linear(piet::LinearGradient {
-            start: (100.0, 100.0).into(),
-            end: (500.0, 300.0).into(),
-            stops: vec![
-                piet::GradientStop {
-                    pos: 0.0,
-                    color: piet::Color::rgb(1.0, 1.0, 1.0),
-                },
-                piet::GradientStop {
-                    pos: 1.0,
-                    color: piet::Color::rgb(0.0, 0.0, 0.0),
-                },
-            ],
-        }))
-        .unwrap();
     let circle = kurbo::Circle::new((100.0, 100.0), 50.0);
     let rect = kurbo::Rect::new(200.0, 100.0, 400.0, 140.0);
     let rounded_rect = kurbo::RoundedRect::new(200.0, 250.0, 400.0, 340.0, 20.0);
@@ -68,7 +43,7 @@ fn main() {
 
     let segoe = device
         .text()
-        .new_font_by_name("Segoe", 49.0)
+        .new_font_by_name("Segoe UI", 49.0)
         .unwrap()
         .build()
         .unwrap();
@@ -79,33 +54,71 @@ fn main() {
         .build()
         .unwrap();
 
+    let mut resources = None;
     let mut stop = false;
     while !stop {
+        let mut resized = false;
         events_loop.poll_events(|event| match event {
             winit::Event::WindowEvent { event, .. } => match event {
                 winit::WindowEvent::CloseRequested => stop = true,
+                winit::WindowEvent::Resized(_) => resized = true,
                 _ => (),
             },
             _ => (),
         });
 
-        device.begin_draw(); // non-generic
+        if resized {
+            swapchain.resize(&device, 0, 0).unwrap();
+        }
+
+        let mut frame = device.begin_frame(&swapchain);
+        frame.set_present(true);
+
+        let (image, brush, gradient) = resources.get_or_insert_with(|| {
+            let image = frame
+                .make_image(
+                    img_width as _,
+                    img_height as _,
+                    &img_data,
+                    ImageFormat::RgbaSeparate,
+                )
+                .unwrap();
+
+            let brush = frame.solid_brush(piet::Color::rgb(1.0, 1.0, 1.0));
+            let gradient = frame
+                .gradient(piet::Gradient::Linear(piet::LinearGradient {
+                    start: (100.0, 100.0).into(),
+                    end: (500.0, 300.0).into(),
+                    stops: vec![
+                        piet::GradientStop {
+                            pos: 0.0,
+                            color: piet::Color::rgb(1.0, 1.0, 1.0),
+                        },
+                        piet::GradientStop {
+                            pos: 1.0,
+                            color: piet::Color::rgb(0.0, 0.0, 0.0),
+                        },
+                    ],
+                }))
+                .unwrap();
+
+            (image, brush, gradient)
+        });
 
-        device.draw_image(
-            &image,
+        frame.draw_image(
+            image,
             piet::kurbo::Rect::new(0.0, 0.0, width as f64, height as f64),
             InterpolationMode::Bilinear,
         );
 
-        device.fill(&circle, &brush, piet::FillRule::NonZero);
-        device.fill(&rect, &brush, piet::FillRule::NonZero);
-        device.fill(&rounded_rect, &brush, piet::FillRule::NonZero);
-        device.fill(&path, &brush, piet::FillRule::NonZero);
-        device.stroke(&path2, &brush, 3.0, None);
+        frame.fill(&circle, brush, piet::FillRule::NonZero);
+        frame.fill(&rect, brush, piet::FillRule::NonZero);
+        frame.fill(&rounded_rect, brush, piet::FillRule::NonZero);
+        frame.fill(&path, brush, piet::FillRule::NonZero);
+        frame.stroke(&path2, brush, 3.0, None);
 
-        device.draw_text(&text_layout, (500.5, 100.325), &brush);
+        frame.draw_text(&text_layout, (500.5, 100.325), brush);
 
-        device.end_draw(); // non-generic
-        swapchain.present();
+        frame.end().unwrap();
     }
 }
@@ -0,0 +1,223 @@
+//! Least recently used cache with limits on the number of entries and their total cost.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+struct Entry<V> {
+    value: V,
+    cost: usize,
+    /// Position in the usage order, larger stamps were used more recently.
+    stamp: u64,
+}
+
+/// Cache evicting the least recently used entries once a limit is exceeded.
+///
+/// The cost of an entry is chosen by the caller, e.g. an estimate of its memory usage.
+pub struct LruCache<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Keys of all entries ordered by last use.
+    order: BTreeMap<u64, K>,
+    next_stamp: u64,
+    max_entries: usize,
+    max_cost: usize,
+    cost: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    pub fn new(max_entries: usize, max_cost: usize) -> Self {
+        LruCache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_stamp: 0,
+            max_entries,
+            max_cost,
+            cost: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Look up an entry and mark it as most recently used, counts a hit or miss.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let stamp = self.next_stamp;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.hits += 1;
+                self.next_stamp += 1;
+                let key = self.order.remove(&entry.stamp).unwrap();
+                self.order.insert(stamp, key);
+                entry.stamp = stamp;
+                Some(&entry.value)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Insert or replace an entry, then evict entries until the cache is within its limits.
+    ///
+    /// Entries exceeding the cost limit on their own are not stored.
+    pub fn insert(&mut self, key: K, value: V, cost: usize) {
+        self.remove(&key);
+        if cost > self.max_cost || self.max_entries == 0 {
+            return;
+        }
+
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+        self.order.insert(stamp, key.clone());
+        self.entries.insert(key, Entry { value, cost, stamp });
+        self.cost += cost;
+        self.evict();
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.stamp);
+        self.cost -= entry.cost;
+        Some(entry.value)
+    }
+
+    /// Change the limits, evicting entries if required.
+    pub fn set_limits(&mut self, max_entries: usize, max_cost: usize) {
+        self.max_entries = max_entries;
+        self.max_cost = max_cost;
+        self.evict();
+    }
+
+    /// Remove all entries, the hit and miss counters are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.cost = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total cost of all entries.
+    pub fn cost(&self) -> usize {
+        self.cost
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn reset_counters(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.max_entries || self.cost > self.max_cost {
+            let stamp = match self.order.keys().next() {
+                Some(&stamp) => stamp,
+                None => break,
+            };
+            let key = self.order.remove(&stamp).unwrap();
+            let entry = self.entries.remove(&key).unwrap();
+            self.cost -= entry.cost;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+
+    #[test]
+    fn hits_and_misses() {
+        let mut cache = LruCache::new(4, 100);
+        assert_eq!(cache.get(&"a"), None);
+        cache.insert("a", 1, 1);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 2);
+
+        cache.reset_counters();
+        assert_eq!(cache.hits(), 0);
+        assert_eq!(cache.misses(), 0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(2, 100);
+        cache.insert("a", 1, 1);
+        cache.insert("b", 2, 1);
+        // Using "a" makes "b" the least recently used entry.
+        assert_eq!(cache.get(&"a"), Some(&1));
+        cache.insert("c", 3, 1);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.get(&"c"), Some(&3));
+    }
+
+    #[test]
+    fn cost_limit() {
+        let mut cache = LruCache::new(10, 10);
+        cache.insert("a", 1, 4);
+        cache.insert("b", 2, 4);
+        assert_eq!(cache.cost(), 8);
+        cache.insert("c", 3, 4);
+        assert_eq!(cache.cost(), 8);
+        assert_eq!(cache.get(&"a"), None);
+
+        // Too large to be stored at all, existing entries are kept.
+        cache.insert("d", 4, 11);
+        assert_eq!(cache.get(&"d"), None);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn replace() {
+        let mut cache = LruCache::new(2, 10);
+        cache.insert("a", 1, 5);
+        cache.insert("a", 2, 3);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.cost(), 3);
+        assert_eq!(cache.get(&"a"), Some(&2));
+        assert_eq!(cache.remove(&"a"), Some(2));
+        assert!(cache.is_empty());
+        assert_eq!(cache.cost(), 0);
+    }
+
+    #[test]
+    fn limits() {
+        let mut cache = LruCache::new(4, 100);
+        for (i, key) in ["a", "b", "c", "d"].iter().enumerate() {
+            cache.insert(*key, i, 10);
+        }
+        cache.set_limits(4, 25);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"c"), Some(&2));
+        assert_eq!(cache.get(&"d"), Some(&3));
+
+        cache.set_limits(0, 25);
+        assert!(cache.is_empty());
+        cache.insert("e", 4, 1);
+        assert!(cache.is_empty());
+
+        cache.set_limits(4, 100);
+        cache.insert("e", 4, 1);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.cost(), 0);
+    }
+}
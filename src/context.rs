@@ -1,14 +1,16 @@
 use crate::sys::direct2d::Brush as D2DBrush;
-use crate::{device::Device, sys, text};
+use crate::{device::Device, swapchain::{ColorSpace, Swapchain}, sys, text};
 use kurbo::{Affine, PathEl, Rect, Shape, Point};
-use piet::{Color, Error, FillRule, Gradient, ImageFormat, InterpolationMode, RoundInto, StrokeStyle};
+use piet::{Color, Error, ErrorKind, FillRule, Gradient, ImageFormat, InterpolationMode, RoundInto, StrokeStyle};
 use winapi::um::d2d1;
 
 pub struct Image(sys::direct2d::Bitmap);
 
+#[derive(Clone)]
 pub enum Brush {
     Solid(sys::direct2d::SolidBrush),
     LinearGradient(sys::direct2d::LinearGradientBrush),
+    RadialGradient(sys::direct2d::RadialGradientBrush),
 }
 
 impl D2DBrush for Brush {
@@ -16,15 +18,362 @@ impl D2DBrush for Brush {
         match *self {
             Brush::Solid(ref brush) => brush.as_brush(),
             Brush::LinearGradient(ref brush) => brush.as_brush(),
+            Brush::RadialGradient(ref brush) => brush.as_brush(),
         }
     }
 }
 
-fn byte_to_frac(byte: u32) -> f32 {
-    ((byte & 0xFF) as f32) * (1.0 / 255.0)
+/// Color space gradient stops are interpolated in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GradientInterpolation {
+    /// Interpolate gamma encoded sRGB values.
+    Srgb,
+    /// Interpolate linear light values.
+    Linear,
 }
 
-impl piet::RenderContext for Device {
+#[derive(Copy, Clone, Debug)]
+pub struct GradientOptions {
+    pub interpolation: GradientInterpolation,
+    pub extend_mode: sys::direct2d::ExtendMode,
+    /// Precision of the gradient ramp texture, `None` selects `Float16` for
+    /// scRGB swapchains and `Unorm8` otherwise.
+    pub buffer_precision: Option<sys::direct2d::BufferPrecision>,
+}
+
+impl Default for GradientOptions {
+    fn default() -> Self {
+        GradientOptions {
+            interpolation: GradientInterpolation::Linear,
+            extend_mode: sys::direct2d::ExtendMode::Clamp,
+            buffer_precision: None,
+        }
+    }
+}
+
+fn affine_to_transform(affine: Affine) -> sys::direct2d::Transform {
+    let [a, b, c, d, e, f] = affine.as_coeffs();
+    sys::direct2d::Transform::new([
+        [a as _, c as _, e as _],
+        [b as _, d as _, f as _],
+    ])
+}
+
+fn create_path_geometry(
+    factory: &sys::direct2d::Factory,
+    shape: impl Shape,
+    fill_mode: sys::direct2d::FillMode,
+) -> sys::direct2d::PathGeometry {
+    let path = shape.to_bez_path(1e-3);
+    let path_geometry = factory.create_path_geometry();
+    {
+        let mut active_figure = false;
+        let mut sink = path_geometry.open();
+        sink.set_fill_mode(fill_mode);
+        for elem in path {
+            match elem {
+                PathEl::MoveTo(p) => {
+                    if active_figure {
+                        sink.end_figure();
+                    }
+
+                    sink.begin_figure([p.x as _, p.y as _]);
+                    active_figure = true;
+                }
+                PathEl::LineTo(p) => {
+                    sink.add_line([p.x as _, p.y as _]);
+                }
+                PathEl::QuadTo(p0, p1) => {
+                    sink.add_quadratic_bezier([p0.x as _, p0.y as _], [p1.x as _, p1.y as _]);
+                }
+                PathEl::CurveTo(p0, p1, p2) => {
+                    sink.add_bezier(
+                        [p0.x as _, p0.y as _],
+                        [p1.x as _, p1.y as _],
+                        [p2.x as _, p2.y as _],
+                    );
+                }
+                PathEl::ClosePath => {
+                    sink.end_figure();
+                    active_figure = false;
+                }
+                _ => unimplemented!(),
+            }
+        }
+
+        if active_figure {
+            sink.end_figure();
+        }
+
+        sink.close();
+    }
+    path_geometry
+}
+
+fn fill_mode(fill_rule: FillRule) -> sys::direct2d::FillMode {
+    match fill_rule {
+        FillRule::NonZero => sys::direct2d::FillMode::Winding,
+        FillRule::EvenOdd => sys::direct2d::FillMode::Alternate,
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct State {
+    transform: Affine,
+    /// Number of clip layers pushed while this state was active.
+    layers: usize,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            transform: Affine::new([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]),
+            layers: 0,
+        }
+    }
+}
+
+/// A single frame drawing into a swapchain.
+///
+/// Created by [`Device::begin_frame`](struct.Device.html#method.begin_frame).
+/// The frame is finished either explicitly via [`end`](#method.end) or
+/// `piet::RenderContext::finish`, or implicitly when the guard is dropped.
+/// Finishing pops all clips left on the stack and ends drawing, the frame
+/// must not be drawn into afterwards.
+pub struct Frame<'a> {
+    device: &'a mut Device,
+    swapchain: &'a Swapchain,
+    states: Vec<State>,
+    text_options: sys::direct2d::DrawTextOptions,
+    present: bool,
+    finished: bool,
+}
+
+impl<'a> Frame<'a> {
+    pub(crate) fn new(device: &'a mut Device, swapchain: &'a Swapchain) -> Self {
+        let context = device.context();
+        context.set_target(swapchain.render_target());
+        context.set_buffer_precision(match swapchain.color_space() {
+            ColorSpace::Srgb => sys::direct2d::BufferPrecision::Unorm8,
+            ColorSpace::ScRgb { .. } => sys::direct2d::BufferPrecision::Float16,
+        });
+        context.set_transform(sys::direct2d::Transform::identity());
+        context.begin_draw();
+
+        Frame {
+            device,
+            swapchain,
+            states: vec![State::default()],
+            text_options: sys::direct2d::DrawTextOptions::ENABLE_COLOR_FONT,
+            present: false,
+            finished: false,
+        }
+    }
+
+    /// Present the swapchain after drawing has successfully ended.
+    pub fn set_present(&mut self, present: bool) {
+        self.present = present;
+    }
+
+    /// Finish the frame and return the result of ending the draw.
+    pub fn end(mut self) -> Result<(), crate::Error> {
+        self.end_frame()
+    }
+
+    fn end_frame(&mut self) -> Result<(), crate::Error> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        let context = self.device.context();
+        while let Some(state) = self.states.pop() {
+            for _ in 0..state.layers {
+                context.pop_layer();
+            }
+        }
+        self.states.push(State::default());
+        context.set_transform(sys::direct2d::Transform::identity());
+        let result = context.end_draw();
+        // Unbind the swapchain so it can be resized or dropped independently
+        // of the other swapchains of the device.
+        context.clear_target();
+        result?;
+
+        if self.present {
+            self.swapchain.present()?;
+        }
+
+        Ok(())
+    }
+
+    /// Antialiasing of text, stays active for subsequent frames of the device.
+    ///
+    /// Text drawn onto transparent surfaces must use grayscale antialiasing.
+    pub fn set_text_antialias_mode(&mut self, mode: sys::direct2d::TextAntialiasMode) {
+        self.device.context().set_text_antialias_mode(mode);
+    }
+
+    /// Gamma, contrast and ClearType parameters for text rendering, stays
+    /// active for subsequent frames of the device. `None` restores the
+    /// parameters of the primary monitor.
+    pub fn set_text_rendering_params(&mut self, params: Option<&sys::dwrite::RenderingParams>) {
+        self.device.context().set_text_rendering_params(params);
+    }
+
+    /// Options used for drawing text in this frame, defaults to `ENABLE_COLOR_FONT`.
+    pub fn set_text_options(&mut self, options: sys::direct2d::DrawTextOptions) {
+        self.text_options = options;
+    }
+
+    /// Draw a text layout with the top-left corner of the layout box at `origin`.
+    ///
+    /// Unlike `draw_text`, the position is independent of the text content.
+    /// Inline objects of the layout are drawn after the text.
+    pub fn draw_text_layout(
+        &mut self,
+        layout: &text::TextLayout,
+        origin: impl Into<Point>,
+        brush: &Brush,
+    ) {
+        let origin: Point = origin.into();
+        layout.clear_inline_objects();
+        self.context().draw_text_layout(
+            [origin.x as _, origin.y as _],
+            &layout.layout,
+            brush,
+            self.text_options,
+        );
+        layout.draw_inline_objects(self);
+    }
+
+    /// Draw glyphs shaped by the caller, `baseline_origin` is the start of the baseline.
+    ///
+    /// Skips building a text layout, the glyph run is drawn as is without
+    /// font fallback, bidi reordering or line breaking. `measuring_mode` must
+    /// match the mode the glyph advances were measured with, custom text
+    /// renderers receive it with each glyph run.
+    pub fn draw_glyph_run(
+        &mut self,
+        glyph_run: &sys::dwrite::GlyphRun,
+        baseline_origin: impl Into<Point>,
+        brush: &Brush,
+        measuring_mode: sys::dwrite::MeasuringMode,
+    ) {
+        let origin: Point = baseline_origin.into();
+        self.context().draw_glyph_run(
+            [origin.x as _, origin.y as _],
+            glyph_run,
+            brush,
+            measuring_mode,
+        );
+    }
+
+    /// Create a new gradient brush with control over interpolation and extend mode.
+    ///
+    /// For scRGB swapchains the SDR white level is applied to the stops in
+    /// either interpolation space. Gradients interpolated in sRGB space need a
+    /// float buffer precision to exceed the brightness of 80 nits.
+    pub fn gradient_with(
+        &mut self,
+        gradient: Gradient,
+        options: GradientOptions,
+    ) -> Result<Brush, crate::Error> {
+        let target_space = self.swapchain.color_space();
+        let pre_interpolation_space = match options.interpolation {
+            GradientInterpolation::Srgb => sys::direct2d::ColorSpace::Srgb,
+            GradientInterpolation::Linear => sys::direct2d::ColorSpace::ScRgb,
+        };
+        let linear_space = match target_space {
+            ColorSpace::ScRgb { .. } => target_space,
+            ColorSpace::Srgb => ColorSpace::ScRgb {
+                sdr_white_level: 80.0,
+            },
+        };
+        let (post_interpolation_space, default_precision) = match target_space {
+            ColorSpace::Srgb => (
+                sys::direct2d::ColorSpace::Srgb,
+                sys::direct2d::BufferPrecision::Unorm8,
+            ),
+            ColorSpace::ScRgb { .. } => (
+                sys::direct2d::ColorSpace::ScRgb,
+                sys::direct2d::BufferPrecision::Float16,
+            ),
+        };
+
+        let stops = match gradient {
+            Gradient::Linear(ref linear) => &linear.stops,
+            Gradient::Radial(ref radial) => &radial.stops,
+        };
+        let stops = stops
+            .iter()
+            .map(|stop| sys::direct2d::GradientStop {
+                position: stop.pos as _,
+                color: match options.interpolation {
+                    GradientInterpolation::Srgb => target_space.convert_encoded(stop.color.clone()),
+                    GradientInterpolation::Linear => linear_space.convert(stop.color.clone()),
+                },
+            })
+            .collect::<Box<[_]>>();
+
+        let context = self.device.context();
+        let gradient_stop_collection = context.create_gradient_stop_collection1(
+            &stops,
+            sys::direct2d::GradientStopProperties {
+                pre_interpolation_space,
+                post_interpolation_space,
+                buffer_precision: options.buffer_precision.unwrap_or(default_precision),
+                extend_mode: options.extend_mode,
+                color_interpolation_mode: sys::direct2d::ColorInterpolationMode::Straight,
+            },
+        )?;
+        match gradient {
+            Gradient::Linear(ref linear) => {
+                let linear = context.create_linear_gradient_brush(
+                    [linear.start.x as _, linear.start.y as _]
+                        ..[linear.end.x as _, linear.end.y as _],
+                    &gradient_stop_collection,
+                    1.0,
+                    sys::direct2d::Transform::identity(),
+                );
+                Ok(Brush::LinearGradient(linear))
+            }
+            Gradient::Radial(ref radial) => {
+                let radial = context.create_radial_gradient_brush(
+                    [radial.center.x as _, radial.center.y as _],
+                    [radial.origin_offset.x as _, radial.origin_offset.y as _],
+                    [radial.radius as _, radial.radius as _],
+                    &gradient_stop_collection,
+                    1.0,
+                    sys::direct2d::Transform::identity(),
+                );
+                Ok(Brush::RadialGradient(radial))
+            }
+        }
+    }
+
+    fn convert_color(&self, color: Color) -> sys::direct2d::Color {
+        self.swapchain.color_space().convert(color)
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        self.states.last_mut().unwrap()
+    }
+
+    /// Device context for drawing commands, which are invalid once the frame is finished.
+    fn context(&self) -> &sys::direct2d::DeviceContext {
+        debug_assert!(!self.finished, "drawing into a finished frame");
+        self.device.context()
+    }
+}
+
+impl<'a> Drop for Frame<'a> {
+    fn drop(&mut self) {
+        let _ = self.end_frame();
+    }
+}
+
+impl<'a> piet::RenderContext for Frame<'a> {
     type Brush = Brush;
     type Text = text::Text;
     type TextLayout = text::TextLayout;
@@ -41,14 +390,8 @@ impl piet::RenderContext for Device {
     }
 
     fn solid_brush(&mut self, rgba: Color) -> Self::Brush {
-        let rgba = rgba.as_rgba32();
-        Brush::Solid(self.create_solid_brush(
-            [
-                byte_to_frac(rgba >> 24),
-                byte_to_frac(rgba >> 16),
-                byte_to_frac(rgba >> 8),
-                byte_to_frac(rgba),
-            ],
+        Brush::Solid(self.device.context().create_solid_brush(
+            self.convert_color(rgba),
             1.0,
             sys::direct2d::Transform::identity(),
         ))
@@ -56,52 +399,14 @@ impl piet::RenderContext for Device {
 
     /// Create a new gradient brush.
     fn gradient(&mut self, gradient: Gradient) -> Result<Self::Brush, Error> {
-        match gradient {
-            Gradient::Linear(ref linear) => {
-                let dir = linear.end - linear.start;
-                let stops = linear
-                    .stops
-                    .iter()
-                    .map(|stop| {
-                        let rgba = stop.color.as_rgba32();
-                        sys::direct2d::GradientStop {
-                        position: stop.pos as _,
-                        color: [
-                            byte_to_frac(rgba >> 24),
-                            byte_to_frac(rgba >> 16),
-                            byte_to_frac(rgba >> 8),
-                            byte_to_frac(rgba),
-                        ],
-                    }
-                    })
-                    .collect::<Box<[_]>>();
-
-                let gradient_stop_collection = self.create_gradient_stop_collection(&stops);
-                let linear = self.create_linear_gradient_brush(
-                    [linear.start.x as _, linear.start.y as _]
-                        ..[linear.end.x as _, linear.end.y as _],
-                    &gradient_stop_collection,
-                    1.0,
-                    sys::direct2d::Transform::identity(),
-                );
-                Ok(Brush::LinearGradient(linear))
-            }
-            Gradient::Radial(ref radial) => unimplemented!(),
-        }
+        let brush = self.gradient_with(gradient, GradientOptions::default())?;
+        Ok(brush)
     }
 
     /// Clear the canvas with the given color.
     fn clear(&mut self, rgba: Color) {
-        let rgba = rgba.as_rgba32();
-        sys::direct2d::DeviceContext::clear(
-            &self,
-            [
-                byte_to_frac(rgba >> 24),
-                byte_to_frac(rgba >> 16),
-                byte_to_frac(rgba >> 8),
-                byte_to_frac(rgba),
-            ],
-        );
+        let color = self.convert_color(rgba);
+        self.context().clear(color);
     }
 
     /// Stroke a shape.
@@ -121,7 +426,7 @@ impl piet::RenderContext for Device {
         } else if let Some(rounded) = shape.as_rounded_rect() {
             let origin = rounded.origin();
             let radius = rounded.radius();
-            self.draw_rounded_rectangle(
+            self.context().draw_rounded_rectangle(
                 brush,
                 sys::direct2d::RoundedRect {
                     rect: sys::direct2d::Rect {
@@ -136,60 +441,19 @@ impl piet::RenderContext for Device {
                 width.round_into(),
             );
         } else {
-            // TODO: move into function
-            let path = shape.to_bez_path(1e-3);
-            let path_geometry = self.d2d_factory.create_path_geometry();
-            {
-                let mut active_figure = false;
-                let mut sink = path_geometry.open();
-                for elem in path {
-                    match elem {
-                        PathEl::MoveTo(p) => {
-                            if active_figure {
-                                sink.end_figure();
-                            }
-
-                            sink.begin_figure([p.x as _, p.y as _]);
-                            active_figure = true;
-                        }
-                        PathEl::LineTo(p) => {
-                            sink.add_line([p.x as _, p.y as _]);
-                        }
-                        PathEl::QuadTo(p0, p1) => {
-                            sink.add_quadratic_bezier(
-                                [p0.x as _, p0.y as _],
-                                [p1.x as _, p1.y as _],
-                            );
-                        }
-                        PathEl::CurveTo(p0, p1, p2) => {
-                            sink.add_bezier(
-                                [p0.x as _, p0.y as _],
-                                [p1.x as _, p1.y as _],
-                                [p2.x as _, p2.y as _],
-                            );
-                        }
-                        PathEl::ClosePath => {
-                            sink.end_figure();
-                            active_figure = false;
-                        }
-                        _ => unimplemented!(),
-                    }
-                }
-
-                if active_figure {
-                    sink.end_figure();
-                }
-
-                sink.close();
-            }
-            self.draw_geometry(&path_geometry, brush, width.round_into());
+            let path_geometry = create_path_geometry(
+                &self.device.d2d_factory,
+                shape,
+                sys::direct2d::FillMode::Winding,
+            );
+            self.context().draw_geometry(&path_geometry, brush, width.round_into());
         }
     }
 
     /// Fill a shape.
-    fn fill(&mut self, shape: impl Shape, brush: &Self::Brush, _fill_rule: FillRule) {
+    fn fill(&mut self, shape: impl Shape, brush: &Self::Brush, fill_rule: FillRule) {
         if let Some(circle) = shape.as_circle() {
-            self.fill_ellipse(
+            self.context().fill_ellipse(
                 brush,
                 sys::direct2d::Ellipse {
                     x: circle.center.x as _,
@@ -199,7 +463,7 @@ impl piet::RenderContext for Device {
                 },
             );
         } else if let Some(rect) = shape.as_rect() {
-            self.fill_rectangle(
+            self.context().fill_rectangle(
                 brush,
                 sys::direct2d::Rect {
                     x: rect.x0 as _,
@@ -211,7 +475,7 @@ impl piet::RenderContext for Device {
         } else if let Some(rounded) = shape.as_rounded_rect() {
             let origin = rounded.origin();
             let radius = rounded.radius();
-            self.fill_rounded_rectangle(
+            self.context().fill_rounded_rectangle(
                 brush,
                 sys::direct2d::RoundedRect {
                     rect: sys::direct2d::Rect {
@@ -225,52 +489,9 @@ impl piet::RenderContext for Device {
                 },
             );
         } else {
-            let path = shape.to_bez_path(1e-3);
-            let path_geometry = self.d2d_factory.create_path_geometry();
-            {
-                let mut active_figure = false;
-                let mut sink = path_geometry.open();
-                for elem in path {
-                    match elem {
-                        PathEl::MoveTo(p) => {
-                            if active_figure {
-                                sink.end_figure();
-                            }
-
-                            sink.begin_figure([p.x as _, p.y as _]);
-                            active_figure = true;
-                        }
-                        PathEl::LineTo(p) => {
-                            sink.add_line([p.x as _, p.y as _]);
-                        }
-                        PathEl::QuadTo(p0, p1) => {
-                            sink.add_quadratic_bezier(
-                                [p0.x as _, p0.y as _],
-                                [p1.x as _, p1.y as _],
-                            );
-                        }
-                        PathEl::CurveTo(p0, p1, p2) => {
-                            sink.add_bezier(
-                                [p0.x as _, p0.y as _],
-                                [p1.x as _, p1.y as _],
-                                [p2.x as _, p2.y as _],
-                            );
-                        }
-                        PathEl::ClosePath => {
-                            sink.end_figure();
-                            active_figure = false;
-                        }
-                        _ => unimplemented!(),
-                    }
-                }
-
-                if active_figure {
-                    sink.end_figure();
-                }
-
-                sink.close();
-            }
-            self.fill_geometry(&path_geometry, brush);
+            let path_geometry =
+                create_path_geometry(&self.device.d2d_factory, shape, fill_mode(fill_rule));
+            self.context().fill_geometry(&path_geometry, brush);
         }
     }
 
@@ -279,25 +500,24 @@ impl piet::RenderContext for Device {
     /// All subsequent drawing operations up to the next [`restore`](#method.restore)
     /// are clipped by the shape.
     fn clip(&mut self, shape: impl Shape, fill_rule: FillRule) {
-        unimplemented!()
+        let path_geometry =
+            create_path_geometry(&self.device.d2d_factory, shape, fill_mode(fill_rule));
+        self.context().push_clip_layer(&path_geometry);
+        self.state_mut().layers += 1;
     }
 
     fn text(&mut self) -> &mut Self::Text {
-        &mut self.dwrite_factory
+        &mut self.device.dwrite_factory
     }
 
     /// Draw a text layout.
     ///
     /// The `pos` parameter specifies the baseline of the left starting place of
     /// the text. Note: this is true even if the text is right-to-left.
-    fn draw_text(
-        &mut self,
-        layout: &Self::TextLayout,
-        pos: impl Into<Point>,
-        brush: &Self::Brush,
-    ) {
+    fn draw_text(&mut self, layout: &Self::TextLayout, pos: impl Into<Point>, brush: &Self::Brush) {
         let pos: Point = pos.into();
-        self.draw_text_layout([pos.x as _, pos.y as _], &layout.0, brush);
+        let origin = Point::new(pos.x, pos.y - layout.first_baseline());
+        self.draw_text_layout(layout, origin, brush);
     }
 
     /// Save the context state.
@@ -311,7 +531,12 @@ impl piet::RenderContext for Device {
     /// The context state currently consists of a clip region and an affine
     /// transform, but is expected to grow in the near future.
     fn save(&mut self) -> Result<(), Error> {
-        unimplemented!()
+        let transform = self.state_mut().transform;
+        self.states.push(State {
+            transform,
+            layers: 0,
+        });
+        Ok(())
     }
 
     /// Restore the context state.
@@ -319,7 +544,17 @@ impl piet::RenderContext for Device {
     /// Pop a context state that was pushed by [`save`](#method.save). See
     /// that method for details.
     fn restore(&mut self) -> Result<(), Error> {
-        unimplemented!()
+        if self.states.len() <= 1 {
+            return Err(piet::new_error(ErrorKind::StackUnbalance));
+        }
+
+        let state = self.states.pop().unwrap();
+        for _ in 0..state.layers {
+            self.context().pop_layer();
+        }
+        let transform = self.state_mut().transform;
+        self.context().set_transform(affine_to_transform(transform));
+        Ok(())
     }
 
     /// Finish any pending operations.
@@ -328,7 +563,8 @@ impl piet::RenderContext for Device {
     /// operations but before presenting. Not all back-ends will handle this
     /// the same way.
     fn finish(&mut self) -> Result<(), Error> {
-        unimplemented!()
+        self.end_frame()?;
+        Ok(())
     }
 
     /// Apply a transform.
@@ -336,7 +572,10 @@ impl piet::RenderContext for Device {
     /// Apply an affine transformation. The transformation remains in effect
     /// until a [`restore`](#method.restore) operation.
     fn transform(&mut self, transform: Affine) {
-        unimplemented!()
+        let state = self.state_mut();
+        state.transform = state.transform * transform;
+        let transform = state.transform;
+        self.context().set_transform(affine_to_transform(transform));
     }
 
     /// Create a new image from a pixel buffer.
@@ -348,7 +587,7 @@ impl piet::RenderContext for Device {
         format: ImageFormat,
     ) -> Result<Self::Image, Error> {
         // TODO: format
-        Ok(Image(self.create_bitmap(
+        Ok(Image(self.device.context().create_bitmap(
             sys::direct2d::Extent {
                 width: width as _,
                 height: height as _,
@@ -372,7 +611,7 @@ impl piet::RenderContext for Device {
         let size = unsafe { image.0.GetSize() };
         let rect: kurbo::Rect = rect.into();
 
-        self.draw_bitmap(
+        self.context().draw_bitmap(
             &image.0,
             sys::direct2d::Rect {
                 x: rect.x0 as _,
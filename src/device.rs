@@ -1,6 +1,9 @@
-use crate::{sys, text};
-use std::ops::Deref;
+use crate::{context::Frame, swapchain::Swapchain, sys, text};
 
+/// Rendering device shared by all swapchains created from it.
+///
+/// Brushes, images, fonts and geometries created through the device can be
+/// used when drawing into any of its swapchains.
 #[allow(dead_code)]
 pub struct Device {
     pub(crate) d3d11_device: sys::d3d11::Device,
@@ -15,7 +18,7 @@ impl Device {
     pub fn create() -> Self {
         let d2d_factory = sys::direct2d::Factory::new();
         let (d3d11_device, d3d11_context) = sys::d3d11::Device::new();
-        let dwrite_factory = text::Text(sys::dwrite::Factory::new());
+        let dwrite_factory = text::Text::new(sys::dwrite::Factory::new());
         let d2d_device = d2d_factory.create_device(&d3d11_device);
         let d2d_context = d2d_device.create_context();
 
@@ -28,11 +31,19 @@ impl Device {
             d3d11_context,
         }
     }
-}
 
-impl Deref for Device {
-    type Target = sys::direct2d::DeviceContext;
-    fn deref(&self) -> &Self::Target {
+    /// Begin drawing a frame into the swapchain.
+    ///
+    /// Drawing ends when the returned frame is finished or dropped.
+    pub fn begin_frame<'a>(&'a mut self, swapchain: &'a Swapchain) -> Frame<'a> {
+        Frame::new(self, swapchain)
+    }
+
+    pub fn text(&mut self) -> &mut text::Text {
+        &mut self.dwrite_factory
+    }
+
+    pub(crate) fn context(&self) -> &sys::direct2d::DeviceContext {
         &self.d2d_context
     }
 }
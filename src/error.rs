@@ -0,0 +1,44 @@
+use std::fmt;
+use winapi::shared::winerror::{HRESULT, SUCCEEDED};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A Windows API call failed with the given `HRESULT`.
+    Hresult(HRESULT),
+    /// The window handle does not refer to a Win32 window.
+    UnsupportedWindowHandle,
+    /// Neither the loaded nor the installed fonts contain the font family.
+    FontFamilyNotFound,
+    /// The swapchain can't present in the requested color space.
+    UnsupportedColorSpace,
+}
+
+impl Error {
+    pub(crate) fn check(hr: HRESULT) -> Result<(), Error> {
+        if SUCCEEDED(hr) {
+            Ok(())
+        } else {
+            Err(Error::Hresult(hr))
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Hresult(hr) => write!(f, "windows api call failed (HRESULT 0x{:08X})", hr),
+            Error::UnsupportedWindowHandle => write!(f, "window handle is not a win32 handle"),
+            Error::FontFamilyNotFound => write!(f, "font family not found"),
+            Error::UnsupportedColorSpace => write!(f, "color space not supported by the swapchain"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for piet::Error {
+    fn from(err: Error) -> piet::Error {
+        let err: Box<dyn std::error::Error> = Box::new(err);
+        err.into()
+    }
+}
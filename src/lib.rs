@@ -1,8 +1,40 @@
+mod cache;
+#[cfg(windows)]
 mod context;
+#[cfg(windows)]
 mod device;
+#[cfg(windows)]
+mod error;
+#[cfg(windows)]
 mod swapchain;
+#[cfg(windows)]
 pub mod sys;
+#[cfg(windows)]
 mod text;
+mod text_buffer;
 
+pub use cache::LruCache;
+#[cfg(windows)]
+pub use context::{Brush, Frame, GradientInterpolation, GradientOptions, Image};
+#[cfg(windows)]
 pub use device::Device;
-pub use swapchain::Swapchain;
+#[cfg(windows)]
+pub use error::Error;
+#[cfg(windows)]
+pub use swapchain::{ColorSpace, Swapchain};
+#[cfg(windows)]
+pub use sys::direct2d::{BufferPrecision, DrawTextOptions, ExtendMode, TextAntialiasMode};
+#[cfg(windows)]
+pub use sys::dwrite::{
+    Decoration, FlowDirection, FontFaceHandle, FontFallback, FontFeatureTag, FontStretch,
+    FontStyle, FontWeight, GlyphMetrics, GlyphOffset, GlyphRun, GlyphRunDescription, MeasuringMode,
+    ParagraphAlignment, PixelGeometry, ReadingDirection, RenderingMode, RenderingParams,
+    TextAlignment, TrimmingGranularity, WordWrapping,
+};
+#[cfg(windows)]
+pub use text::{
+    glyph_run_outline, ClusterMetrics, FontFaceInfo, FontFallbackBuilder, FontFamilyInfo,
+    FontMetrics, HitTestPoint, HitTestPosition, LineMetrics, LineSpacing, TextLayoutCache,
+    TextRenderer,
+};
+pub use text_buffer::TextBuffer;
@@ -1,28 +1,186 @@
 use crate::device::Device;
-use crate::sys;
+use crate::{sys, Error};
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
-#[allow(dead_code)]
+struct Target {
+    // Keep the backbuffer alive alongside the bitmap wrapping it.
+    _backbuffer: sys::dxgi::BackbufferRaw,
+    render_target: sys::direct2d::Bitmap,
+}
+
+impl Target {
+    fn new(device: &Device, swapchain: &sys::dxgi::Swapchain) -> Self {
+        let backbuffer = swapchain.get_backbuffer();
+        let render_target = device.context().create_bitmap_from_backbuffer(&backbuffer);
+        Target {
+            _backbuffer: backbuffer,
+            render_target,
+        }
+    }
+}
+
+/// Color space of the swapchain contents.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// 8-bit sRGB output.
+    Srgb,
+    /// 16-bit float linear scRGB output for HDR and wide gamut displays.
+    ///
+    /// `sdr_white_level` is the brightness of sRGB white in nits,
+    /// a value of 1.0 in scRGB corresponds to 80 nits.
+    ScRgb { sdr_white_level: f32 },
+}
+
+impl ColorSpace {
+    /// Convert an sRGB color into the color space.
+    pub(crate) fn convert(&self, color: piet::Color) -> sys::direct2d::Color {
+        let rgba = color.as_rgba32();
+        let channel = |shift: u32| ((rgba >> shift) & 0xFF) as f32 * (1.0 / 255.0);
+        let (r, g, b, a) = (channel(24), channel(16), channel(8), channel(0));
+
+        match *self {
+            ColorSpace::Srgb => [r, g, b, a],
+            ColorSpace::ScRgb { sdr_white_level } => {
+                let scale = sdr_white_level / 80.0;
+                let linear = |c: f32| srgb_to_linear(c) * scale;
+                [linear(r), linear(g), linear(b), a]
+            }
+        }
+    }
+
+    /// Convert an sRGB color into gamma encoded sRGB values matching the
+    /// brightness of the color space.
+    ///
+    /// For scRGB the white level is applied to the linear values before
+    /// encoding them again, values exceed 1.0 for levels above 80 nits.
+    pub(crate) fn convert_encoded(&self, color: piet::Color) -> sys::direct2d::Color {
+        match *self {
+            ColorSpace::Srgb => self.convert(color),
+            ColorSpace::ScRgb { .. } => {
+                let [r, g, b, a] = self.convert(color);
+                [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a]
+            }
+        }
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Window surface drawn into by a [`Device`](struct.Device.html).
+///
+/// A device may drive any number of swapchains, each frame selects the
+/// target via `Device::begin_frame`. Resources created by the device are
+/// shared between all of its swapchains.
 pub struct Swapchain {
     swapchain: sys::dxgi::Swapchain,
-    backbuffer: sys::dxgi::BackbufferRaw,
-    render_target: sys::direct2d::Bitmap,
+    target: Option<Target>,
+    color_space: ColorSpace,
 }
 
 impl Swapchain {
     pub fn create_from_hwnd(device: &Device, hwnd: winapi::shared::windef::HWND) -> Self {
         let swapchain = sys::dxgi::Swapchain::create_from_hwnd(&device.d3d11_device, hwnd);
-        let backbuffer = swapchain.get_backbuffer();
-        let render_target = device.create_bitmap_from_backbuffer(&backbuffer);
-        device.set_target(&render_target);
+        let target = Target::new(device, &swapchain);
 
         Swapchain {
             swapchain,
-            backbuffer,
-            render_target,
+            target: Some(target),
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    /// Create a swapchain for any window exposing a raw window handle.
+    ///
+    /// Returns `Error::UnsupportedWindowHandle` for non-Win32 windows.
+    pub fn create(device: &Device, window: &impl HasRawWindowHandle) -> Result<Self, Error> {
+        match window.raw_window_handle() {
+            RawWindowHandle::Windows(handle) if !handle.hwnd.is_null() => {
+                Ok(Swapchain::create_from_hwnd(device, handle.hwnd as *mut _))
+            }
+            _ => Err(Error::UnsupportedWindowHandle),
+        }
+    }
+
+    /// Current size of the backbuffers in pixels.
+    pub fn extent(&self) -> (u32, u32) {
+        self.swapchain.get_extent()
+    }
+
+    /// Resize the backbuffers after the window has been resized.
+    ///
+    /// Passing zero for width and height uses the size of the window's client area.
+    /// Must not be called while a frame is drawing into this swapchain.
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) -> Result<(), Error> {
+        // Release all references to the old backbuffer before resizing.
+        self.target = None;
+        let result = self.swapchain.resize_buffers(width, height, None);
+        self.target = Some(Target::new(device, &self.swapchain));
+        result
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Switch between sRGB and scRGB output.
+    ///
+    /// Recreates the backbuffers with a matching format. Colors passed to
+    /// frames drawing into this swapchain are converted into the color space.
+    /// Must not be called while a frame is drawing into this swapchain.
+    ///
+    /// Returns `Error::UnsupportedColorSpace` without changing the swapchain
+    /// if the color space can't be presented, e.g. scRGB on older systems.
+    pub fn set_color_space(
+        &mut self,
+        device: &Device,
+        color_space: ColorSpace,
+    ) -> Result<(), Error> {
+        let (format, dxgi_color_space) = match color_space {
+            ColorSpace::Srgb => (
+                sys::dxgi::Format::B8G8R8A8Unorm,
+                sys::dxgi::ColorSpace::RgbFullG22P709,
+            ),
+            ColorSpace::ScRgb { .. } => (
+                sys::dxgi::Format::R16G16B16A16Float,
+                sys::dxgi::ColorSpace::RgbFullG10P709,
+            ),
+        };
+        if !self.swapchain.check_color_space_support(dxgi_color_space)? {
+            return Err(Error::UnsupportedColorSpace);
         }
+
+        let (width, height) = self.extent();
+        self.target = None;
+        let result = self
+            .swapchain
+            .resize_buffers(width, height, Some(format))
+            .and_then(|()| self.swapchain.set_color_space(dxgi_color_space));
+        self.target = Some(Target::new(device, &self.swapchain));
+        result?;
+
+        self.color_space = color_space;
+        Ok(())
+    }
+
+    pub fn present(&self) -> Result<(), Error> {
+        self.swapchain.present()
     }
 
-    pub fn present(&self) {
-        self.swapchain.present();
+    pub(crate) fn render_target(&self) -> &sys::direct2d::Bitmap {
+        &self.target.as_ref().unwrap().render_target
     }
 }
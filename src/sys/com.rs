@@ -0,0 +1,83 @@
+//! Helpers for implementing COM interfaces in Rust.
+
+use std::ptr;
+use std::sync::atomic::{self, AtomicU32, Ordering};
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, GUID, REFIID};
+use winapi::shared::minwindef::ULONG;
+use winapi::shared::winerror::{E_NOINTERFACE, E_POINTER, HRESULT, S_OK};
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+use wio::com::ComPtr;
+
+/// Data of a COM object implemented in Rust.
+///
+/// The `IUnknown` part of `VTBL` must point to `query_interface`, `add_ref`
+/// and `release` of this module, instantiated for the implementing type.
+pub unsafe trait Class: Sized {
+    type Vtbl: 'static;
+    const VTBL: &'static Self::Vtbl;
+
+    /// Whether `QueryInterface` succeeds for the interface, `IUnknown` is always supported.
+    fn supports(iid: &GUID) -> bool;
+}
+
+/// Reference counted COM object, compatible with the layout of the interfaces.
+#[repr(C)]
+pub struct Object<T: Class> {
+    vtbl: *const T::Vtbl,
+    refs: AtomicU32,
+    data: T,
+}
+
+impl<T: Class> Object<T> {
+    /// Allocate a new object, the returned pointer holds the only reference.
+    pub fn create<I: Interface>(data: T) -> ComPtr<I> {
+        let object = Box::new(Object {
+            vtbl: T::VTBL,
+            refs: AtomicU32::new(1),
+            data,
+        });
+        unsafe { ComPtr::from_raw(Box::into_raw(object) as *mut I) }
+    }
+
+    /// Access the object data through an interface pointer.
+    ///
+    /// The pointer must refer to an object created with `T`.
+    pub unsafe fn from_interface<'a, I>(this: *mut I) -> &'a T {
+        &(*(this as *const Object<T>)).data
+    }
+}
+
+pub unsafe extern "system" fn query_interface<T: Class>(
+    this: *mut IUnknown,
+    iid: REFIID,
+    object: *mut *mut c_void,
+) -> HRESULT {
+    if object.is_null() {
+        return E_POINTER;
+    }
+    if IsEqualGUID(&*iid, &IUnknown::uuidof()) || T::supports(&*iid) {
+        add_ref::<T>(this);
+        *object = this as *mut _;
+        S_OK
+    } else {
+        *object = ptr::null_mut();
+        E_NOINTERFACE
+    }
+}
+
+pub unsafe extern "system" fn add_ref<T: Class>(this: *mut IUnknown) -> ULONG {
+    let object = &*(this as *const Object<T>);
+    object.refs.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+pub unsafe extern "system" fn release<T: Class>(this: *mut IUnknown) -> ULONG {
+    let object = &*(this as *const Object<T>);
+    let refs = object.refs.fetch_sub(1, Ordering::Release) - 1;
+    if refs == 0 {
+        atomic::fence(Ordering::Acquire);
+        drop(Box::from_raw(this as *mut Object<T>));
+    }
+    refs
+}
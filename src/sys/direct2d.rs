@@ -1,8 +1,10 @@
 use super::d3d11;
-use super::dwrite::{TextFormat, TextLayout};
+use super::dwrite::{GlyphRun, MeasuringMode, RenderingParams, TextFormat, TextLayout};
 use super::dxgi::BackbufferRaw;
-use std::ops::Deref;
+use crate::Error;
+use std::mem;
 use std::ops::Range;
+use std::ops::{BitOr, Deref};
 use std::ptr;
 use winapi::shared::{dxgi, dxgiformat::*};
 use winapi::um::{d2d1, d2d1_1, d2d1_3, dcommon};
@@ -15,9 +17,23 @@ pub type TransformRaw = [[f32; 3]; 2];
 pub struct Transform(TransformRaw);
 
 impl Transform {
+    pub fn new(raw: TransformRaw) -> Self {
+        Transform(raw)
+    }
+
     pub fn identity() -> Self {
         Transform([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
     }
+
+    fn as_matrix(&self) -> d2d1::D2D1_MATRIX_3X2_F {
+        d2d1::D2D1_MATRIX_3X2_F {
+            matrix: [
+                [self.0[0][0], self.0[1][0]],
+                [self.0[0][1], self.0[1][1]],
+                [self.0[0][2], self.0[1][2]],
+            ],
+        }
+    }
 }
 
 impl Deref for Transform {
@@ -46,6 +62,54 @@ pub struct GradientStop {
     pub color: Color,
 }
 
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExtendMode {
+    Clamp = d2d1::D2D1_EXTEND_MODE_CLAMP,
+    Wrap = d2d1::D2D1_EXTEND_MODE_WRAP,
+    Mirror = d2d1::D2D1_EXTEND_MODE_MIRROR,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb = d2d1_1::D2D1_COLOR_SPACE_SRGB,
+    ScRgb = d2d1_1::D2D1_COLOR_SPACE_SCRGB,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorInterpolationMode {
+    Straight = d2d1_1::D2D1_COLOR_INTERPOLATION_MODE_STRAIGHT,
+    Premultiplied = d2d1_1::D2D1_COLOR_INTERPOLATION_MODE_PREMULTIPLIED,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GradientStopProperties {
+    /// Color space the stops are specified and interpolated in.
+    pub pre_interpolation_space: ColorSpace,
+    /// Color space the interpolated colors are converted into.
+    pub post_interpolation_space: ColorSpace,
+    pub buffer_precision: BufferPrecision,
+    pub extend_mode: ExtendMode,
+    pub color_interpolation_mode: ColorInterpolationMode,
+}
+
+fn gradient_stops_to_d2d(stops: &[GradientStop]) -> Vec<d2d1::D2D1_GRADIENT_STOP> {
+    stops
+        .iter()
+        .map(|stop| d2d1::D2D1_GRADIENT_STOP {
+            position: stop.position,
+            color: d2d1::D2D1_COLOR_F {
+                r: stop.color[0],
+                g: stop.color[1],
+                b: stop.color[2],
+                a: stop.color[3],
+            },
+        })
+        .collect()
+}
+
 pub type GradientStopCollectionRaw = ComPtr<d2d1::ID2D1GradientStopCollection>;
 pub struct GradientStopCollection(GradientStopCollectionRaw);
 
@@ -82,6 +146,60 @@ pub struct RoundedRect {
     pub radius_y: f32,
 }
 
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BufferPrecision {
+    Unorm8 = d2d1_1::D2D1_BUFFER_PRECISION_8BPC_UNORM,
+    Unorm8Srgb = d2d1_1::D2D1_BUFFER_PRECISION_8BPC_UNORM_SRGB,
+    Unorm16 = d2d1_1::D2D1_BUFFER_PRECISION_16BPC_UNORM,
+    Float16 = d2d1_1::D2D1_BUFFER_PRECISION_16BPC_FLOAT,
+    Float32 = d2d1_1::D2D1_BUFFER_PRECISION_32BPC_FLOAT,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug)]
+pub enum FillMode {
+    Alternate = d2d1::D2D1_FILL_MODE_ALTERNATE,
+    Winding = d2d1::D2D1_FILL_MODE_WINDING,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextAntialiasMode {
+    /// Use the system settings, usually ClearType.
+    Default = d2d1::D2D1_TEXT_ANTIALIAS_MODE_DEFAULT,
+    ClearType = d2d1::D2D1_TEXT_ANTIALIAS_MODE_CLEARTYPE,
+    /// Required when drawing onto transparent surfaces.
+    Grayscale = d2d1::D2D1_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+    Aliased = d2d1::D2D1_TEXT_ANTIALIAS_MODE_ALIASED,
+}
+
+/// Options for drawing text, combined with `|`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DrawTextOptions(u32);
+
+impl DrawTextOptions {
+    pub const NONE: Self = DrawTextOptions(d2d1::D2D1_DRAW_TEXT_OPTIONS_NONE);
+    /// Don't snap the baseline to pixels, used for animated text.
+    pub const NO_SNAP: Self = DrawTextOptions(d2d1::D2D1_DRAW_TEXT_OPTIONS_NO_SNAP);
+    /// Clip the text to the layout box.
+    pub const CLIP: Self = DrawTextOptions(d2d1::D2D1_DRAW_TEXT_OPTIONS_CLIP);
+    /// Draw color glyphs, e.g. emoji, in color.
+    pub const ENABLE_COLOR_FONT: Self =
+        DrawTextOptions(d2d1::D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for DrawTextOptions {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        DrawTextOptions(self.0 | other.0)
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Ellipse {
     pub x: f32,
@@ -177,6 +295,39 @@ impl DeviceContext {
         }
     }
 
+    /// Unbind the current target, releasing the context's reference to it.
+    pub fn clear_target(&self) {
+        unsafe {
+            self.SetTarget(ptr::null());
+        }
+    }
+
+    pub fn set_text_antialias_mode(&self, mode: TextAntialiasMode) {
+        unsafe { self.SetTextAntialiasMode(mode as _) }
+    }
+
+    /// Set the parameters for text rendering, `None` restores the defaults.
+    pub fn set_text_rendering_params(&self, params: Option<&RenderingParams>) {
+        let params = params.map_or(ptr::null_mut(), |params| params.as_raw());
+        unsafe { self.SetTextRenderingParams(params) }
+    }
+
+    /// Set the precision of intermediate buffers used for layers and effects.
+    pub fn set_buffer_precision(&self, precision: BufferPrecision) {
+        unsafe {
+            let mut controls = mem::zeroed();
+            self.GetRenderingControls(&mut controls);
+            controls.bufferPrecision = precision as _;
+            self.SetRenderingControls(&controls);
+        }
+    }
+
+    pub fn set_transform(&self, transform: Transform) {
+        unsafe {
+            self.SetTransform(&transform.as_matrix());
+        }
+    }
+
     pub fn create_solid_brush(&self, c: Color, opacity: f32, transform: Transform) -> SolidBrush {
         let properties = d2d1::D2D1_BRUSH_PROPERTIES {
             opacity,
@@ -204,35 +355,69 @@ impl DeviceContext {
         }
     }
 
-    pub fn create_gradient_stop_collection(
+    /// Create a gradient stop collection with explicit interpolation color spaces.
+    ///
+    /// Stops are specified with straight alpha in the pre-interpolation color space.
+    pub fn create_gradient_stop_collection1(
         &self,
         stops: &[GradientStop],
-    ) -> GradientStopCollection {
-        let stops = stops
-            .iter()
-            .map(|stop| d2d1::D2D1_GRADIENT_STOP {
-                position: stop.position,
-                color: d2d1::D2D1_COLOR_F {
-                    r: stop.color[0],
-                    g: stop.color[1],
-                    b: stop.color[2],
-                    a: stop.color[3],
-                },
-            })
-            .collect::<Vec<_>>();
+        properties: GradientStopProperties,
+    ) -> Result<GradientStopCollection, Error> {
+        let stops = gradient_stops_to_d2d(stops);
         unsafe {
             let mut collection = ptr::null_mut();
-            let _hr = self
-                .cast::<d2d1::ID2D1RenderTarget>()
-                .unwrap()
-                .CreateGradientStopCollection(
-                    stops.as_ptr(),
-                    stops.len() as _,
-                    d2d1::D2D1_GAMMA_1_0,
-                    d2d1::D2D1_EXTEND_MODE_CLAMP,
-                    &mut collection as *mut _,
-                );
-            GradientStopCollection(GradientStopCollectionRaw::from_raw(collection))
+            let hr = self.CreateGradientStopCollection(
+                stops.as_ptr(),
+                stops.len() as _,
+                properties.pre_interpolation_space as _,
+                properties.post_interpolation_space as _,
+                properties.buffer_precision as _,
+                properties.extend_mode as _,
+                properties.color_interpolation_mode as _,
+                &mut collection as *mut _,
+            );
+            Error::check(hr)?;
+            let collection = ComPtr::<d2d1_1::ID2D1GradientStopCollection1>::from_raw(collection);
+            Ok(GradientStopCollection(collection.up()))
+        }
+    }
+
+    /// `origin_offset` moves the gradient origin relative to the center of the ellipse.
+    pub fn create_radial_gradient_brush(
+        &self,
+        center: Point,
+        origin_offset: Point,
+        radius: [f32; 2],
+        stops: &GradientStopCollection,
+        opacity: f32,
+        transform: Transform,
+    ) -> RadialGradientBrush {
+        let brush_properties = d2d1::D2D1_BRUSH_PROPERTIES {
+            opacity,
+            transform: d2d1::D2D1_MATRIX_3X2_F {
+                matrix: [
+                    [transform[0][0], transform[1][0]],
+                    [transform[0][1], transform[1][1]],
+                    [transform[0][2], transform[1][2]],
+                ],
+            },
+        };
+
+        let properties = d2d1::D2D1_RADIAL_GRADIENT_BRUSH_PROPERTIES {
+            center: point_to_d2d(center),
+            gradientOriginOffset: point_to_d2d(origin_offset),
+            radiusX: radius[0],
+            radiusY: radius[1],
+        };
+        unsafe {
+            let mut brush = ptr::null_mut();
+            let _hr = self.CreateRadialGradientBrush(
+                &properties,
+                &brush_properties,
+                stops.as_raw(),
+                &mut brush as *mut _,
+            );
+            RadialGradientBrush(RadialGradientBrushRaw::from_raw(brush))
         }
     }
 
@@ -368,6 +553,8 @@ impl DeviceContext {
         text_format: &TextFormat,
         layout: Rect,
         brush: &impl Brush,
+        options: DrawTextOptions,
+        measuring_mode: MeasuringMode,
     ) {
         let text = text.to_wide();
         let r = layout.as_rect_f();
@@ -378,13 +565,19 @@ impl DeviceContext {
                 text_format.as_raw(),
                 &r,
                 brush.as_brush(),
-                d2d1::D2D1_DRAW_TEXT_OPTIONS_NONE,
-                dcommon::DWRITE_MEASURING_MODE_NATURAL,
+                options.0,
+                measuring_mode as _,
             );
         }
     }
 
-    pub fn draw_text_layout(&self, origin: Point, text_layout: &TextLayout, brush: &impl Brush) {
+    pub fn draw_text_layout(
+        &self,
+        origin: Point,
+        text_layout: &TextLayout,
+        brush: &impl Brush,
+        options: DrawTextOptions,
+    ) {
         unsafe {
             self.DrawTextLayout(
                 point_to_d2d(origin),
@@ -392,14 +585,63 @@ impl DeviceContext {
                 brush.as_brush(),
                 ptr::null_mut(), // TODO
                 0,               // TODO
-                d2d1::D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT,
+                options.0,
+            );
+        }
+    }
+
+    /// Draw glyphs positioned by the caller, `baseline_origin` is the start of the baseline.
+    pub fn draw_glyph_run(
+        &self,
+        baseline_origin: Point,
+        glyph_run: &GlyphRun,
+        brush: &impl Brush,
+        measuring_mode: MeasuringMode,
+    ) {
+        let run = glyph_run.as_raw();
+        unsafe {
+            self.DrawGlyphRun(
+                point_to_d2d(baseline_origin),
+                &run,
+                ptr::null(),
+                brush.as_brush(),
+                measuring_mode as _,
             );
         }
     }
 
-    pub fn end_draw(&self) {
+    pub fn end_draw(&self) -> Result<(), Error> {
+        let hr = unsafe { self.EndDraw(ptr::null_mut(), ptr::null_mut()) };
+        Error::check(hr)
+    }
+
+    /// Push a layer clipping all subsequent drawing to the geometry.
+    ///
+    /// The geometry is interpreted in the current world transform.
+    pub fn push_clip_layer(&self, geometry: &PathGeometry) {
+        let infinite = d2d1::D2D1_RECT_F {
+            left: -std::f32::MAX,
+            top: -std::f32::MAX,
+            right: std::f32::MAX,
+            bottom: std::f32::MAX,
+        };
+        let parameters = d2d1_1::D2D1_LAYER_PARAMETERS1 {
+            contentBounds: infinite,
+            geometricMask: geometry.as_raw() as *const _,
+            maskAntialiasMode: d2d1::D2D1_ANTIALIAS_MODE_PER_PRIMITIVE,
+            maskTransform: Transform::identity().as_matrix(),
+            opacity: 1.0,
+            opacityBrush: ptr::null(),
+            layerOptions: d2d1_1::D2D1_LAYER_OPTIONS1_NONE,
+        };
+        unsafe {
+            self.PushLayer(&parameters, ptr::null());
+        }
+    }
+
+    pub fn pop_layer(&self) {
         unsafe {
-            self.EndDraw(ptr::null_mut(), ptr::null_mut());
+            self.PopLayer();
         }
     }
 
@@ -507,6 +749,7 @@ pub trait Brush {
 }
 
 pub type SolidBrushRaw = ComPtr<d2d1::ID2D1SolidColorBrush>;
+#[derive(Clone)]
 pub struct SolidBrush(SolidBrushRaw);
 
 impl Brush for SolidBrush {
@@ -523,6 +766,7 @@ impl Deref for SolidBrush {
 }
 
 pub type LinearGradientBrushRaw = ComPtr<d2d1::ID2D1LinearGradientBrush>;
+#[derive(Clone)]
 pub struct LinearGradientBrush(LinearGradientBrushRaw);
 
 impl Brush for LinearGradientBrush {
@@ -538,6 +782,23 @@ impl Deref for LinearGradientBrush {
     }
 }
 
+pub type RadialGradientBrushRaw = ComPtr<d2d1::ID2D1RadialGradientBrush>;
+#[derive(Clone)]
+pub struct RadialGradientBrush(RadialGradientBrushRaw);
+
+impl Brush for RadialGradientBrush {
+    fn as_brush(&self) -> *mut d2d1::ID2D1Brush {
+        self.as_raw() as *mut _
+    }
+}
+
+impl Deref for RadialGradientBrush {
+    type Target = RadialGradientBrushRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 pub type LayerRaw = ComPtr<d2d1::ID2D1Layer>;
 pub struct Layer(LayerRaw);
 
@@ -559,6 +820,12 @@ impl Deref for GeometrySink {
 }
 
 impl GeometrySink {
+    pub fn set_fill_mode(&mut self, mode: FillMode) {
+        unsafe {
+            self.SetFillMode(mode as _);
+        }
+    }
+
     pub fn begin_figure(&mut self, start: Point) {
         // TODO
         unsafe {
@@ -1,16 +1,38 @@
+use super::com;
+use super::direct2d::Brush;
+use crate::Error;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::mem;
-use std::ops::Deref;
+use std::ops::{Deref, Range, RangeInclusive};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use std::ptr;
-use winapi::um::dwrite;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, GUID};
+use winapi::shared::minwindef::{BOOL, FALSE, TRUE};
+use winapi::shared::winerror::{E_FAIL, E_INVALIDARG, E_NOTIMPL, HRESULT, S_OK};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::um::winnls::GetUserDefaultLocaleName;
+use winapi::um::winnt::LOCALE_NAME_MAX_LENGTH;
+use winapi::um::{d2d1, dcommon, dwrite, dwrite_1, dwrite_2};
 use winapi::Interface;
 use wio::com::ComPtr;
 use wio::wide::ToWide;
 
 pub type FactoryRaw = ComPtr<dwrite::IDWriteFactory>;
-pub struct Factory(FactoryRaw);
+#[derive(Clone)]
+pub struct Factory {
+    factory: FactoryRaw,
+    collection_loader: Rc<CollectionLoaderRegistration>,
+}
 
 #[repr(u32)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum FontWeight {
     Thin = dwrite::DWRITE_FONT_WEIGHT_THIN,
     UltraLight = dwrite::DWRITE_FONT_WEIGHT_ULTRA_LIGHT,
@@ -25,6 +47,244 @@ pub enum FontWeight {
     UltraBlack = dwrite::DWRITE_FONT_WEIGHT_ULTRA_BLACK,
 }
 
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    Normal = dwrite::DWRITE_FONT_STYLE_NORMAL,
+    Oblique = dwrite::DWRITE_FONT_STYLE_OBLIQUE,
+    Italic = dwrite::DWRITE_FONT_STYLE_ITALIC,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FontStretch {
+    UltraCondensed = dwrite::DWRITE_FONT_STRETCH_ULTRA_CONDENSED,
+    ExtraCondensed = dwrite::DWRITE_FONT_STRETCH_EXTRA_CONDENSED,
+    Condensed = dwrite::DWRITE_FONT_STRETCH_CONDENSED,
+    SemiCondensed = dwrite::DWRITE_FONT_STRETCH_SEMI_CONDENSED,
+    Normal = dwrite::DWRITE_FONT_STRETCH_NORMAL,
+    SemiExpanded = dwrite::DWRITE_FONT_STRETCH_SEMI_EXPANDED,
+    Expanded = dwrite::DWRITE_FONT_STRETCH_EXPANDED,
+    ExtraExpanded = dwrite::DWRITE_FONT_STRETCH_EXTRA_EXPANDED,
+    UltraExpanded = dwrite::DWRITE_FONT_STRETCH_ULTRA_EXPANDED,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WordWrapping {
+    Wrap = dwrite::DWRITE_WORD_WRAPPING_WRAP,
+    NoWrap = dwrite::DWRITE_WORD_WRAPPING_NO_WRAP,
+    EmergencyBreak = dwrite::DWRITE_WORD_WRAPPING_EMERGENCY_BREAK,
+    WholeWord = dwrite::DWRITE_WORD_WRAPPING_WHOLE_WORD,
+    Character = dwrite::DWRITE_WORD_WRAPPING_CHARACTER,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextAlignment {
+    Leading = dwrite::DWRITE_TEXT_ALIGNMENT_LEADING,
+    Trailing = dwrite::DWRITE_TEXT_ALIGNMENT_TRAILING,
+    Center = dwrite::DWRITE_TEXT_ALIGNMENT_CENTER,
+    Justified = dwrite::DWRITE_TEXT_ALIGNMENT_JUSTIFIED,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParagraphAlignment {
+    Near = dwrite::DWRITE_PARAGRAPH_ALIGNMENT_NEAR,
+    Far = dwrite::DWRITE_PARAGRAPH_ALIGNMENT_FAR,
+    Center = dwrite::DWRITE_PARAGRAPH_ALIGNMENT_CENTER,
+}
+
+/// Direction of characters inside a line.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReadingDirection {
+    LeftToRight = dwrite::DWRITE_READING_DIRECTION_LEFT_TO_RIGHT,
+    RightToLeft = dwrite::DWRITE_READING_DIRECTION_RIGHT_TO_LEFT,
+    TopToBottom = dwrite::DWRITE_READING_DIRECTION_TOP_TO_BOTTOM,
+    BottomToTop = dwrite::DWRITE_READING_DIRECTION_BOTTOM_TO_TOP,
+}
+
+/// Direction in which lines are stacked.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FlowDirection {
+    TopToBottom = dwrite::DWRITE_FLOW_DIRECTION_TOP_TO_BOTTOM,
+    BottomToTop = dwrite::DWRITE_FLOW_DIRECTION_BOTTOM_TO_TOP,
+    LeftToRight = dwrite::DWRITE_FLOW_DIRECTION_LEFT_TO_RIGHT,
+    RightToLeft = dwrite::DWRITE_FLOW_DIRECTION_RIGHT_TO_LEFT,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineSpacingMethod {
+    /// Line height is derived from the fonts used in each line.
+    Default = dwrite::DWRITE_LINE_SPACING_METHOD_DEFAULT,
+    /// Line height and baseline are absolute values.
+    Uniform = dwrite::DWRITE_LINE_SPACING_METHOD_UNIFORM,
+    /// Line height and baseline are factors of the font-derived values, Windows 10 only.
+    Proportional = dwrite::DWRITE_LINE_SPACING_METHOD_PROPORTIONAL,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TrimmingGranularity {
+    None = dwrite::DWRITE_TRIMMING_GRANULARITY_NONE,
+    Character = dwrite::DWRITE_TRIMMING_GRANULARITY_CHARACTER,
+    Word = dwrite::DWRITE_TRIMMING_GRANULARITY_WORD,
+}
+
+/// OpenType feature tag, e.g. `tnum` for tabular figures.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontFeatureTag(pub u32);
+
+impl FontFeatureTag {
+    pub const KERNING: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_KERNING);
+    pub const STANDARD_LIGATURES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_STANDARD_LIGATURES);
+    pub const CONTEXTUAL_LIGATURES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_CONTEXTUAL_LIGATURES);
+    pub const DISCRETIONARY_LIGATURES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_DISCRETIONARY_LIGATURES);
+    pub const CONTEXTUAL_ALTERNATES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_CONTEXTUAL_ALTERNATES);
+    pub const STYLISTIC_ALTERNATES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_STYLISTIC_ALTERNATES);
+    pub const SWASH: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_SWASH);
+    pub const SMALL_CAPITALS: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_SMALL_CAPITALS);
+    pub const SMALL_CAPITALS_FROM_CAPITALS: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_SMALL_CAPITALS_FROM_CAPITALS);
+    pub const TABULAR_FIGURES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_TABULAR_FIGURES);
+    pub const PROPORTIONAL_FIGURES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_PROPORTIONAL_FIGURES);
+    pub const LINING_FIGURES: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_LINING_FIGURES);
+    pub const OLD_STYLE_FIGURES: Self =
+        FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_OLD_STYLE_FIGURES);
+    pub const SLASHED_ZERO: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_SLASHED_ZERO);
+    pub const FRACTIONS: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_FRACTIONS);
+    pub const SUBSCRIPT: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_SUBSCRIPT);
+    pub const SUPERSCRIPT: Self = FontFeatureTag(dwrite::DWRITE_FONT_FEATURE_TAG_SUPERSCRIPT);
+
+    /// Tag from its four characters, e.g. `b"ss01"`.
+    pub fn new(tag: [u8; 4]) -> Self {
+        FontFeatureTag(u32::from_le_bytes(tag))
+    }
+
+    /// Stylistic set `ss01` to `ss20`, `None` for sets outside of 1 to 20.
+    pub fn stylistic_set(set: u8) -> Option<Self> {
+        if (1..=20).contains(&set) {
+            Some(FontFeatureTag::new([
+                b's',
+                b's',
+                b'0' + set / 10,
+                b'0' + set % 10,
+            ]))
+        } else {
+            None
+        }
+    }
+}
+
+impl FontWeight {
+    /// Closest named weight, fonts may use any weight between 1 and 999.
+    pub fn from_raw(weight: u32) -> Self {
+        match weight {
+            0..=149 => FontWeight::Thin,
+            150..=249 => FontWeight::UltraLight,
+            250..=324 => FontWeight::Light,
+            325..=374 => FontWeight::SemiLeght,
+            375..=449 => FontWeight::Normal,
+            450..=549 => FontWeight::Medium,
+            550..=649 => FontWeight::SemiBold,
+            650..=749 => FontWeight::Bold,
+            750..=849 => FontWeight::UltraBold,
+            850..=924 => FontWeight::Black,
+            _ => FontWeight::UltraBlack,
+        }
+    }
+}
+
+impl FontStyle {
+    pub fn from_raw(style: u32) -> Self {
+        match style {
+            dwrite::DWRITE_FONT_STYLE_OBLIQUE => FontStyle::Oblique,
+            dwrite::DWRITE_FONT_STYLE_ITALIC => FontStyle::Italic,
+            _ => FontStyle::Normal,
+        }
+    }
+}
+
+impl FontStretch {
+    pub fn from_raw(stretch: u32) -> Self {
+        match stretch {
+            dwrite::DWRITE_FONT_STRETCH_ULTRA_CONDENSED => FontStretch::UltraCondensed,
+            dwrite::DWRITE_FONT_STRETCH_EXTRA_CONDENSED => FontStretch::ExtraCondensed,
+            dwrite::DWRITE_FONT_STRETCH_CONDENSED => FontStretch::Condensed,
+            dwrite::DWRITE_FONT_STRETCH_SEMI_CONDENSED => FontStretch::SemiCondensed,
+            dwrite::DWRITE_FONT_STRETCH_SEMI_EXPANDED => FontStretch::SemiExpanded,
+            dwrite::DWRITE_FONT_STRETCH_EXPANDED => FontStretch::Expanded,
+            dwrite::DWRITE_FONT_STRETCH_EXTRA_EXPANDED => FontStretch::ExtraExpanded,
+            dwrite::DWRITE_FONT_STRETCH_ULTRA_EXPANDED => FontStretch::UltraExpanded,
+            _ => FontStretch::Normal,
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MeasuringMode {
+    /// Resolution independent glyph metrics.
+    Natural = dcommon::DWRITE_MEASURING_MODE_NATURAL,
+    /// Glyph metrics compatible with GDI text rendering.
+    GdiClassic = dcommon::DWRITE_MEASURING_MODE_GDI_CLASSIC,
+    /// Glyph metrics compatible with GDI text rendering using natural advances.
+    GdiNatural = dcommon::DWRITE_MEASURING_MODE_GDI_NATURAL,
+}
+
+impl MeasuringMode {
+    pub fn from_raw(mode: u32) -> Self {
+        match mode {
+            dcommon::DWRITE_MEASURING_MODE_GDI_CLASSIC => MeasuringMode::GdiClassic,
+            dcommon::DWRITE_MEASURING_MODE_GDI_NATURAL => MeasuringMode::GdiNatural,
+            _ => MeasuringMode::Natural,
+        }
+    }
+}
+
+/// Subpixel order of the display, used for ClearType.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelGeometry {
+    Flat = dwrite::DWRITE_PIXEL_GEOMETRY_FLAT,
+    Rgb = dwrite::DWRITE_PIXEL_GEOMETRY_RGB,
+    Bgr = dwrite::DWRITE_PIXEL_GEOMETRY_BGR,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderingMode {
+    Default = dwrite::DWRITE_RENDERING_MODE_DEFAULT,
+    Aliased = dwrite::DWRITE_RENDERING_MODE_ALIASED,
+    GdiClassic = dwrite::DWRITE_RENDERING_MODE_GDI_CLASSIC,
+    GdiNatural = dwrite::DWRITE_RENDERING_MODE_GDI_NATURAL,
+    Natural = dwrite::DWRITE_RENDERING_MODE_NATURAL,
+    NaturalSymmetric = dwrite::DWRITE_RENDERING_MODE_NATURAL_SYMMETRIC,
+    Outline = dwrite::DWRITE_RENDERING_MODE_OUTLINE,
+}
+
+/// Locale name of the current user, e.g. `en-US`.
+pub fn user_default_locale() -> String {
+    let mut locale = [0u16; LOCALE_NAME_MAX_LENGTH];
+    let len = unsafe { GetUserDefaultLocaleName(locale.as_mut_ptr(), locale.len() as _) };
+    if len <= 1 {
+        return "en-US".into();
+    }
+    // Length includes the terminating NUL.
+    String::from_utf16_lossy(&locale[..len as usize - 1])
+}
+
 impl Factory {
     pub fn new() -> Self {
         unsafe {
@@ -35,55 +295,278 @@ impl Factory {
                 &mut factory as *mut _ as *mut *mut _,
             );
 
-            Factory(FactoryRaw::from_raw(factory))
+            let factory = FactoryRaw::from_raw(factory);
+            let collection_loader = Rc::new(CollectionLoaderRegistration::register(&factory));
+            Factory {
+                factory,
+                collection_loader,
+            }
+        }
+    }
+
+    /// Collection of the fonts installed on the system.
+    pub fn get_system_font_collection(&self) -> Result<FontCollection, Error> {
+        unsafe {
+            let mut collection = ptr::null_mut();
+            let hr = self.GetSystemFontCollection(&mut collection, FALSE);
+            Error::check(hr)?;
+            Ok(FontCollection::from_raw(collection))
+        }
+    }
+
+    /// Font fallback used by text layouts without custom fallback, requires Windows 8.1.
+    pub fn get_system_font_fallback(&self) -> Result<FontFallback, Error> {
+        let factory = self
+            .cast::<dwrite_2::IDWriteFactory2>()
+            .map_err(Error::Hresult)?;
+        unsafe {
+            let mut fallback = ptr::null_mut();
+            let hr = factory.GetSystemFontFallback(&mut fallback);
+            Error::check(hr)?;
+            Ok(FontFallback(FontFallbackRaw::from_raw(fallback)))
+        }
+    }
+
+    /// Requires Windows 8.1.
+    pub fn create_font_fallback_builder(&self) -> Result<FontFallbackBuilder, Error> {
+        let factory = self
+            .cast::<dwrite_2::IDWriteFactory2>()
+            .map_err(Error::Hresult)?;
+        unsafe {
+            let mut builder = ptr::null_mut();
+            let hr = factory.CreateFontFallbackBuilder(&mut builder);
+            Error::check(hr)?;
+            Ok(FontFallbackBuilder(FontFallbackBuilderRaw::from_raw(
+                builder,
+            )))
+        }
+    }
+
+    /// Reference a font file on disk.
+    pub fn create_font_file_reference(&self, path: &Path) -> Result<FontFile, Error> {
+        let path = path.as_os_str().to_wide_null();
+        unsafe {
+            let mut file = ptr::null_mut();
+            let hr = self.CreateFontFileReference(path.as_ptr(), ptr::null(), &mut file);
+            Error::check(hr)?;
+            Ok(FontFile {
+                file: FontFileRaw::from_raw(file),
+                _loader: None,
+            })
+        }
+    }
+
+    /// Create a font file from font data in memory, e.g. the contents of a TTF or OTF file.
+    ///
+    /// The data is owned by a font file loader of its own, which is released
+    /// together with the last DirectWrite object using the file, e.g. font
+    /// faces, text formats and text layouts.
+    pub fn create_font_file_from_memory(&self, data: &[u8]) -> Result<FontFile, Error> {
+        let loader: ComPtr<dwrite::IDWriteFontFileLoader> = com::Object::create(MemoryFontLoader {
+            data: Arc::from(data),
+        });
+        let loader = Rc::new(FileLoaderRegistration::register(&self.factory, loader)?);
+        // Each loader serves a single file, the key only has to be consistent.
+        let key = 0u64;
+        unsafe {
+            let mut file = ptr::null_mut();
+            let hr = self.CreateCustomFontFileReference(
+                &key as *const u64 as *const _,
+                mem::size_of::<u64>() as _,
+                loader.loader.as_raw(),
+                &mut file,
+            );
+            Error::check(hr)?;
+            Ok(FontFile {
+                file: FontFileRaw::from_raw(file),
+                _loader: Some(loader),
+            })
         }
     }
 
+    /// Create a font collection containing all fonts of the files.
+    ///
+    /// Collections are immutable, load multiple files at once instead of
+    /// recreating collections with an additional file.
+    pub fn create_font_collection(&self, files: &[FontFile]) -> Result<FontCollection, Error> {
+        let key = self.collection_loader.next_key();
+        let loader = unsafe {
+            com::Object::<CollectionLoader>::from_interface(self.collection_loader.loader.as_raw())
+        };
+        loader
+            .collections
+            .lock()
+            .unwrap()
+            .insert(key, files.to_vec());
+        let result = unsafe {
+            let mut collection = ptr::null_mut();
+            let hr = self.CreateCustomFontCollection(
+                self.collection_loader.loader.as_raw(),
+                &key as *const u64 as *const _,
+                mem::size_of::<u64>() as _,
+                &mut collection,
+            );
+            Error::check(hr).map(|()| FontCollection {
+                collection: FontCollectionRaw::from_raw(collection),
+                _files: files.into(),
+                _loader: Some(self.collection_loader.clone()),
+            })
+        };
+        // Files are only enumerated while creating the collection.
+        loader.collections.lock().unwrap().remove(&key);
+        result
+    }
+
+    /// Create a text format, the font family is looked up in the collection or
+    /// in the system font collection if `None`.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_text_format(
         &self,
         font_family: &str,
+        collection: Option<&FontCollection>,
         size: f32,
         font_weight: FontWeight,
-    ) -> TextFormat {
+        font_style: FontStyle,
+        font_stretch: FontStretch,
+        locale: &str,
+    ) -> Result<TextFormat, Error> {
         let font_family = font_family.to_wide_null();
-        let locale = "en-GB".to_wide_null(); // TODO
+        let locale = locale.to_wide_null();
         unsafe {
             let mut text_format = ptr::null_mut();
-            let _hr = self.CreateTextFormat(
+            let hr = self.CreateTextFormat(
                 font_family.as_ptr(),
-                ptr::null_mut(),
+                collection.map_or(ptr::null_mut(), |collection| collection.as_raw()),
                 font_weight as _,
-                dwrite::DWRITE_FONT_STYLE_NORMAL,   // TODO
-                dwrite::DWRITE_FONT_STRETCH_NORMAL, // TODO
+                font_style as _,
+                font_stretch as _,
                 size,
                 locale.as_ptr(),
                 &mut text_format as *mut _,
             );
+            Error::check(hr)?;
 
-            TextFormat(TextFormatRaw::from_raw(text_format))
+            Ok(TextFormat(TextFormatRaw::from_raw(text_format)))
         }
     }
 
+    /// Create a layout for UTF-16 encoded text.
     pub fn create_text_layout(
         &self,
-        text: &str,
+        text: &[u16],
+        format: &TextFormat,
+        width: f32,
+        height: f32,
+    ) -> Result<TextLayout, Error> {
+        unsafe {
+            let mut layout = ptr::null_mut();
+            let hr = self.CreateTextLayout(
+                text.as_ptr(),
+                text.len() as _,
+                format.as_raw(),
+                width,
+                height,
+                &mut layout as *mut _,
+            );
+            Error::check(hr)?;
+
+            Ok(TextLayout(TextLayoutRaw::from_raw(layout)))
+        }
+    }
+
+    pub fn create_typography(&self) -> Result<Typography, Error> {
+        unsafe {
+            let mut typography = ptr::null_mut();
+            let hr = self.CreateTypography(&mut typography);
+            Error::check(hr)?;
+            Ok(Typography(TypographyRaw::from_raw(typography)))
+        }
+    }
+
+    pub fn create_text_analyzer(&self) -> Result<TextAnalyzer, Error> {
+        unsafe {
+            let mut analyzer = ptr::null_mut();
+            let hr = self.CreateTextAnalyzer(&mut analyzer);
+            Error::check(hr)?;
+            Ok(TextAnalyzer(TextAnalyzerRaw::from_raw(analyzer)))
+        }
+    }
+
+    /// Create a layout with glyph metrics compatible with GDI text rendering.
+    ///
+    /// `pixels_per_dip` is the number of physical pixels per DIP, e.g. 1.5 at 144 DPI.
+    pub fn create_gdi_compatible_text_layout(
+        &self,
+        text: &[u16],
         format: &TextFormat,
         width: f32,
         height: f32,
-    ) -> TextLayout {
-        let text = text.to_wide_null();
+        pixels_per_dip: f32,
+        use_gdi_natural: bool,
+    ) -> Result<TextLayout, Error> {
         unsafe {
             let mut layout = ptr::null_mut();
-            let _hr = self.CreateTextLayout(
+            let hr = self.CreateGdiCompatibleTextLayout(
                 text.as_ptr(),
                 text.len() as _,
                 format.as_raw(),
                 width,
                 height,
+                pixels_per_dip,
+                ptr::null(),
+                use_gdi_natural as _,
                 &mut layout as *mut _,
             );
+            Error::check(hr)?;
+
+            Ok(TextLayout(TextLayoutRaw::from_raw(layout)))
+        }
+    }
+
+    /// Rendering parameters of the primary monitor.
+    pub fn create_rendering_params(&self) -> Result<RenderingParams, Error> {
+        unsafe {
+            let mut params = ptr::null_mut();
+            let hr = self.CreateRenderingParams(&mut params);
+            Error::check(hr)?;
+            Ok(RenderingParams(RenderingParamsRaw::from_raw(params)))
+        }
+    }
+
+    /// Create rendering parameters, `cleartype_level` ranges from 0 (grayscale) to 1 (full ClearType).
+    pub fn create_custom_rendering_params(
+        &self,
+        gamma: f32,
+        enhanced_contrast: f32,
+        cleartype_level: f32,
+        pixel_geometry: PixelGeometry,
+        rendering_mode: RenderingMode,
+    ) -> Result<RenderingParams, Error> {
+        unsafe {
+            let mut params = ptr::null_mut();
+            let hr = self.CreateCustomRenderingParams(
+                gamma,
+                enhanced_contrast,
+                cleartype_level,
+                pixel_geometry as _,
+                rendering_mode as _,
+                &mut params,
+            );
+            Error::check(hr)?;
+            Ok(RenderingParams(RenderingParamsRaw::from_raw(params)))
+        }
+    }
 
-            TextLayout(TextLayoutRaw::from_raw(layout))
+    /// Create an ellipsis sign matching the font of the format, used for trimming.
+    pub fn create_ellipsis_trimming_sign(
+        &self,
+        format: &TextFormat,
+    ) -> Result<InlineObject, Error> {
+        unsafe {
+            let mut sign = ptr::null_mut();
+            let hr = self.CreateEllipsisTrimmingSign(format.as_raw(), &mut sign);
+            Error::check(hr)?;
+            Ok(InlineObject(InlineObjectRaw::from_raw(sign)))
         }
     }
 }
@@ -91,63 +574,2116 @@ impl Factory {
 impl Deref for Factory {
     type Target = FactoryRaw;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.factory
     }
 }
 
-pub type TextFormatRaw = ComPtr<dwrite::IDWriteTextFormat>;
-pub struct TextFormat(TextFormatRaw);
+/// Collection loader implemented by us, registered with the factory.
+///
+/// Stays registered as long as the factory or any collection created with it exists.
+struct CollectionLoaderRegistration {
+    factory: FactoryRaw,
+    loader: ComPtr<dwrite::IDWriteFontCollectionLoader>,
+    next_key: Cell<u64>,
+}
 
-impl Deref for TextFormat {
-    type Target = TextFormatRaw;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl CollectionLoaderRegistration {
+    fn register(factory: &FactoryRaw) -> Self {
+        let loader: ComPtr<dwrite::IDWriteFontCollectionLoader> =
+            com::Object::create(CollectionLoader {
+                collections: Mutex::new(HashMap::new()),
+            });
+        unsafe {
+            let _hr = factory.RegisterFontCollectionLoader(loader.as_raw());
+        }
+
+        CollectionLoaderRegistration {
+            factory: factory.clone(),
+            loader,
+            next_key: Cell::new(0),
+        }
+    }
+
+    /// Keys are never reused as DirectWrite caches collections by key.
+    fn next_key(&self) -> u64 {
+        let key = self.next_key.get();
+        self.next_key.set(key + 1);
+        key
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct OverhangMetrics {
-    pub left: f32,
-    pub right: f32,
-    pub top: f32,
-    pub bottom: f32,
+impl Drop for CollectionLoaderRegistration {
+    fn drop(&mut self) {
+        unsafe {
+            let _hr = self
+                .factory
+                .UnregisterFontCollectionLoader(self.loader.as_raw());
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct TextMetrics {
-    pub left: f32,
-    pub top: f32,
-    pub width: f32,
-    pub width_incl_trailing_whitespaces: f32,
-    pub height: f32,
-    pub layout_width: f32,
-    pub layout_height: f32,
-    pub max_bidi_reordering_depth: u32,
-    pub line_count: u32,
+/// Memory font loader of a single file, registered with the factory.
+///
+/// Stays registered as long as the font file or any collection containing it
+/// exists. DirectWrite keeps its own reference to the loader afterwards.
+struct FileLoaderRegistration {
+    factory: FactoryRaw,
+    loader: ComPtr<dwrite::IDWriteFontFileLoader>,
 }
 
-pub type TextLayoutRaw = ComPtr<dwrite::IDWriteTextLayout>;
-pub struct TextLayout(TextLayoutRaw);
+impl FileLoaderRegistration {
+    fn register(
+        factory: &FactoryRaw,
+        loader: ComPtr<dwrite::IDWriteFontFileLoader>,
+    ) -> Result<Self, Error> {
+        let hr = unsafe { factory.RegisterFontFileLoader(loader.as_raw()) };
+        Error::check(hr)?;
+        Ok(FileLoaderRegistration {
+            factory: factory.clone(),
+            loader,
+        })
+    }
+}
 
-impl TextLayout {
-    pub fn get_metrics(&self) -> TextMetrics {
+impl Drop for FileLoaderRegistration {
+    fn drop(&mut self) {
         unsafe {
-            let mut metrics = mem::zeroed();
-            let _hr = self.GetMetrics(&mut metrics);
-            TextMetrics {
-                left: metrics.left,
-                top: metrics.top,
-                width: metrics.width,
-                width_incl_trailing_whitespaces: metrics.widthIncludingTrailingWhitespace,
-                height: metrics.height,
-                layout_width: metrics.layoutWidth,
-                layout_height: metrics.layoutHeight,
-                max_bidi_reordering_depth: metrics.maxBidiReorderingDepth,
-                line_count: metrics.lineCount,
+            let _hr = self.factory.UnregisterFontFileLoader(self.loader.as_raw());
+        }
+    }
+}
+
+/// Read the `u64` key passed to our loaders.
+unsafe fn loader_key(key: *const c_void, key_size: u32) -> Option<u64> {
+    if key.is_null() || key_size as usize != mem::size_of::<u64>() {
+        None
+    } else {
+        Some(ptr::read_unaligned(key as *const u64))
+    }
+}
+
+/// Font file loader serving the data of a single font file from memory.
+///
+/// The data is freed once the loader and all streams opened by it are released.
+struct MemoryFontLoader {
+    data: Arc<[u8]>,
+}
+
+unsafe impl com::Class for MemoryFontLoader {
+    type Vtbl = dwrite::IDWriteFontFileLoaderVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteFontFileLoaderVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        CreateStreamFromKey: Self::create_stream_from_key,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteFontFileLoader::uuidof())
+    }
+}
+
+impl MemoryFontLoader {
+    unsafe extern "system" fn create_stream_from_key(
+        this: *mut dwrite::IDWriteFontFileLoader,
+        key: *const c_void,
+        key_size: u32,
+        stream: *mut *mut dwrite::IDWriteFontFileStream,
+    ) -> HRESULT {
+        *stream = ptr::null_mut();
+        let loader = com::Object::<Self>::from_interface(this);
+        match loader_key(key, key_size) {
+            Some(_) => {
+                let data = loader.data.clone();
+                *stream =
+                    com::Object::create::<dwrite::IDWriteFontFileStream>(MemoryFontStream { data })
+                        .into_raw();
+                S_OK
+            }
+            None => E_INVALIDARG,
+        }
+    }
+}
+
+struct MemoryFontStream {
+    data: Arc<[u8]>,
+}
+
+unsafe impl com::Class for MemoryFontStream {
+    type Vtbl = dwrite::IDWriteFontFileStreamVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteFontFileStreamVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        ReadFileFragment: Self::read_file_fragment,
+        ReleaseFileFragment: Self::release_file_fragment,
+        GetFileSize: Self::get_file_size,
+        GetLastWriteTime: Self::get_last_write_time,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteFontFileStream::uuidof())
+    }
+}
+
+impl MemoryFontStream {
+    unsafe extern "system" fn read_file_fragment(
+        this: *mut dwrite::IDWriteFontFileStream,
+        fragment_start: *mut *const c_void,
+        offset: u64,
+        size: u64,
+        fragment_context: *mut *mut c_void,
+    ) -> HRESULT {
+        let data = &com::Object::<Self>::from_interface(this).data;
+        *fragment_context = ptr::null_mut();
+        match offset.checked_add(size) {
+            Some(end) if end <= data.len() as u64 => {
+                *fragment_start = data[offset as usize..].as_ptr() as *const _;
+                S_OK
+            }
+            _ => {
+                *fragment_start = ptr::null();
+                E_INVALIDARG
+            }
+        }
+    }
+
+    unsafe extern "system" fn release_file_fragment(
+        _this: *mut dwrite::IDWriteFontFileStream,
+        _fragment_context: *mut c_void,
+    ) {
+    }
+
+    unsafe extern "system" fn get_file_size(
+        this: *mut dwrite::IDWriteFontFileStream,
+        size: *mut u64,
+    ) -> HRESULT {
+        *size = com::Object::<Self>::from_interface(this).data.len() as _;
+        S_OK
+    }
+
+    unsafe extern "system" fn get_last_write_time(
+        _this: *mut dwrite::IDWriteFontFileStream,
+        last_write_time: *mut u64,
+    ) -> HRESULT {
+        // Data in memory has no modification time.
+        *last_write_time = 0;
+        E_NOTIMPL
+    }
+}
+
+/// Font collection loader enumerating the files passed to `create_font_collection`.
+struct CollectionLoader {
+    collections: Mutex<HashMap<u64, Vec<FontFile>>>,
+}
+
+unsafe impl com::Class for CollectionLoader {
+    type Vtbl = dwrite::IDWriteFontCollectionLoaderVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteFontCollectionLoaderVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        CreateEnumeratorFromKey: Self::create_enumerator_from_key,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteFontCollectionLoader::uuidof())
+    }
+}
+
+impl CollectionLoader {
+    unsafe extern "system" fn create_enumerator_from_key(
+        this: *mut dwrite::IDWriteFontCollectionLoader,
+        _factory: *mut dwrite::IDWriteFactory,
+        key: *const c_void,
+        key_size: u32,
+        enumerator: *mut *mut dwrite::IDWriteFontFileEnumerator,
+    ) -> HRESULT {
+        *enumerator = ptr::null_mut();
+        let loader = com::Object::<Self>::from_interface(this);
+        let collections = match loader.collections.lock() {
+            Ok(collections) => collections,
+            Err(_) => return E_FAIL,
+        };
+        match loader_key(key, key_size).and_then(|key| collections.get(&key)) {
+            Some(files) => {
+                *enumerator =
+                    com::Object::create::<dwrite::IDWriteFontFileEnumerator>(FontFileEnumerator {
+                        files: files.clone(),
+                        next: Cell::new(0),
+                    })
+                    .into_raw();
+                S_OK
+            }
+            None => E_INVALIDARG,
+        }
+    }
+}
+
+struct FontFileEnumerator {
+    files: Vec<FontFile>,
+    /// Index of the file after the current one, the enumerator starts before the first file.
+    next: Cell<usize>,
+}
+
+unsafe impl com::Class for FontFileEnumerator {
+    type Vtbl = dwrite::IDWriteFontFileEnumeratorVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteFontFileEnumeratorVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        MoveNext: Self::move_next,
+        GetCurrentFontFile: Self::get_current_font_file,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteFontFileEnumerator::uuidof())
+    }
+}
+
+impl FontFileEnumerator {
+    unsafe extern "system" fn move_next(
+        this: *mut dwrite::IDWriteFontFileEnumerator,
+        has_current_file: *mut BOOL,
+    ) -> HRESULT {
+        let enumerator = com::Object::<Self>::from_interface(this);
+        let next = enumerator.next.get();
+        if next < enumerator.files.len() {
+            enumerator.next.set(next + 1);
+            *has_current_file = TRUE;
+        } else {
+            enumerator.next.set(enumerator.files.len() + 1);
+            *has_current_file = FALSE;
+        }
+        S_OK
+    }
+
+    unsafe extern "system" fn get_current_font_file(
+        this: *mut dwrite::IDWriteFontFileEnumerator,
+        font_file: *mut *mut dwrite::IDWriteFontFile,
+    ) -> HRESULT {
+        let enumerator = com::Object::<Self>::from_interface(this);
+        let current = enumerator.next.get().wrapping_sub(1);
+        match enumerator.files.get(current) {
+            Some(file) => {
+                *font_file = file.file.clone().into_raw();
+                S_OK
             }
+            None => {
+                *font_file = ptr::null_mut();
+                E_FAIL
+            }
+        }
+    }
+}
+
+pub type FontFileRaw = ComPtr<dwrite::IDWriteFontFile>;
+#[derive(Clone)]
+pub struct FontFile {
+    file: FontFileRaw,
+    /// Loader of files created from memory.
+    _loader: Option<Rc<FileLoaderRegistration>>,
+}
+
+impl Deref for FontFile {
+    type Target = FontFileRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.file
+    }
+}
+
+pub type FontCollectionRaw = ComPtr<dwrite::IDWriteFontCollection>;
+#[derive(Clone)]
+pub struct FontCollection {
+    collection: FontCollectionRaw,
+    /// Files of custom collections, keeping the loaders of the files registered.
+    _files: Rc<[FontFile]>,
+    /// Loader of custom collections.
+    _loader: Option<Rc<CollectionLoaderRegistration>>,
+}
+
+impl FontCollection {
+    unsafe fn from_raw(collection: *mut dwrite::IDWriteFontCollection) -> Self {
+        FontCollection {
+            collection: FontCollectionRaw::from_raw(collection),
+            _files: Rc::new([]),
+            _loader: None,
         }
     }
 
+    /// Index of the font family with the name.
+    pub fn find_family_name(&self, family: &str) -> Option<u32> {
+        let family = family.to_wide_null();
+        unsafe {
+            let mut index = 0;
+            let mut exists = FALSE;
+            let hr = self.FindFamilyName(family.as_ptr(), &mut index, &mut exists);
+            if Error::check(hr).is_ok() && exists != FALSE {
+                Some(index)
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn get_font_family_count(&self) -> u32 {
+        unsafe { self.GetFontFamilyCount() }
+    }
+
+    pub fn get_font_family(&self, index: u32) -> Result<FontFamily, Error> {
+        unsafe {
+            let mut family = ptr::null_mut();
+            let hr = self.GetFontFamily(index, &mut family);
+            Error::check(hr)?;
+            Ok(FontFamily(FontFamilyRaw::from_raw(family)))
+        }
+    }
+}
+
+impl Deref for FontCollection {
+    type Target = FontCollectionRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.collection
+    }
+}
+
+pub type FontFamilyRaw = ComPtr<dwrite::IDWriteFontFamily>;
+#[derive(Clone)]
+pub struct FontFamily(FontFamilyRaw);
+
+impl FontFamily {
+    pub fn get_family_names(&self) -> Result<LocalizedStrings, Error> {
+        unsafe {
+            let mut names = ptr::null_mut();
+            let hr = self.GetFamilyNames(&mut names);
+            Error::check(hr)?;
+            Ok(LocalizedStrings(LocalizedStringsRaw::from_raw(names)))
+        }
+    }
+
+    pub fn get_font_count(&self) -> u32 {
+        unsafe { self.GetFontCount() }
+    }
+
+    pub fn get_font(&self, index: u32) -> Result<Font, Error> {
+        unsafe {
+            let mut font = ptr::null_mut();
+            let hr = self.GetFont(index, &mut font);
+            Error::check(hr)?;
+            Ok(Font(FontRaw::from_raw(font)))
+        }
+    }
+
+    /// Font of the family matching the properties best.
+    pub fn get_first_matching_font(
+        &self,
+        weight: FontWeight,
+        stretch: FontStretch,
+        style: FontStyle,
+    ) -> Result<Font, Error> {
+        unsafe {
+            let mut font = ptr::null_mut();
+            let hr = self.GetFirstMatchingFont(weight as _, stretch as _, style as _, &mut font);
+            Error::check(hr)?;
+            Ok(Font(FontRaw::from_raw(font)))
+        }
+    }
+}
+
+impl Deref for FontFamily {
+    type Target = FontFamilyRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type FontRaw = ComPtr<dwrite::IDWriteFont>;
+#[derive(Clone)]
+pub struct Font(FontRaw);
+
+impl Font {
+    pub fn get_weight(&self) -> FontWeight {
+        FontWeight::from_raw(unsafe { self.GetWeight() })
+    }
+
+    pub fn get_style(&self) -> FontStyle {
+        FontStyle::from_raw(unsafe { self.GetStyle() })
+    }
+
+    pub fn get_stretch(&self) -> FontStretch {
+        FontStretch::from_raw(unsafe { self.GetStretch() })
+    }
+
+    pub fn is_symbol_font(&self) -> bool {
+        unsafe { self.IsSymbolFont() != FALSE }
+    }
+
+    /// Names of the face inside the family, e.g. `Bold Italic`.
+    pub fn get_face_names(&self) -> Result<LocalizedStrings, Error> {
+        unsafe {
+            let mut names = ptr::null_mut();
+            let hr = self.GetFaceNames(&mut names);
+            Error::check(hr)?;
+            Ok(LocalizedStrings(LocalizedStringsRaw::from_raw(names)))
+        }
+    }
+
+    pub fn create_font_face(&self) -> Result<FontFace, Error> {
+        unsafe {
+            let mut face = ptr::null_mut();
+            let hr = self.CreateFontFace(&mut face);
+            Error::check(hr)?;
+            Ok(FontFace(FontFaceRaw::from_raw(face)))
+        }
+    }
+}
+
+impl Deref for Font {
+    type Target = FontRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Font metrics in design units.
+#[derive(Copy, Clone, Debug)]
+pub struct FontMetrics {
+    pub design_units_per_em: u16,
+    pub ascent: u16,
+    pub descent: u16,
+    pub line_gap: i16,
+    pub cap_height: u16,
+    pub x_height: u16,
+    /// Offset of the underline from the baseline, positive values are above the baseline.
+    pub underline_position: i16,
+    pub underline_thickness: u16,
+    /// Offset of the strikethrough from the baseline, positive values are above the baseline.
+    pub strikethrough_position: i16,
+    pub strikethrough_thickness: u16,
+}
+
+pub type FontFaceRaw = ComPtr<dwrite::IDWriteFontFace>;
+#[derive(Clone)]
+pub struct FontFace(FontFaceRaw);
+
+impl FontFace {
+    pub fn get_metrics(&self) -> FontMetrics {
+        unsafe {
+            let mut metrics = mem::zeroed();
+            self.GetMetrics(&mut metrics);
+            FontMetrics {
+                design_units_per_em: metrics.designUnitsPerEm,
+                ascent: metrics.ascent,
+                descent: metrics.descent,
+                line_gap: metrics.lineGap,
+                cap_height: metrics.capHeight,
+                x_height: metrics.xHeight,
+                underline_position: metrics.underlinePosition,
+                underline_thickness: metrics.underlineThickness,
+                strikethrough_position: metrics.strikethroughPosition,
+                strikethrough_thickness: metrics.strikethroughThickness,
+            }
+        }
+    }
+
+    /// Number of glyphs in the font.
+    pub fn get_glyph_count(&self) -> u16 {
+        unsafe { self.GetGlyphCount() }
+    }
+
+    /// Glyph index of each code point, zero for code points missing in the font.
+    pub fn get_glyph_indices(&self, code_points: &[u32]) -> Result<Vec<u16>, Error> {
+        let mut indices = vec![0; code_points.len()];
+        Error::check(unsafe {
+            self.GetGlyphIndices(
+                code_points.as_ptr(),
+                code_points.len() as _,
+                indices.as_mut_ptr(),
+            )
+        })?;
+        Ok(indices)
+    }
+
+    /// Metrics of the glyphs, scale by the font size divided by
+    /// `design_units_per_em` to get DIPs.
+    pub fn get_design_glyph_metrics(
+        &self,
+        glyph_indices: &[u16],
+        is_sideways: bool,
+    ) -> Result<Vec<GlyphMetrics>, Error> {
+        let mut metrics = Vec::with_capacity(glyph_indices.len());
+        unsafe {
+            let hr = self.GetDesignGlyphMetrics(
+                glyph_indices.as_ptr(),
+                glyph_indices.len() as _,
+                metrics.as_mut_ptr(),
+                is_sideways as _,
+            );
+            Error::check(hr)?;
+            metrics.set_len(glyph_indices.len());
+        }
+        Ok(metrics
+            .into_iter()
+            .map(|m: dwrite::DWRITE_GLYPH_METRICS| GlyphMetrics {
+                left_side_bearing: m.leftSideBearing,
+                advance_width: m.advanceWidth,
+                right_side_bearing: m.rightSideBearing,
+                top_side_bearing: m.topSideBearing,
+                advance_height: m.advanceHeight,
+                bottom_side_bearing: m.bottomSideBearing,
+                vertical_origin_y: m.verticalOriginY,
+            })
+            .collect())
+    }
+}
+
+/// Glyph metrics in design units.
+#[derive(Copy, Clone, Debug)]
+pub struct GlyphMetrics {
+    pub left_side_bearing: i32,
+    pub advance_width: u32,
+    pub right_side_bearing: i32,
+    pub top_side_bearing: i32,
+    pub advance_height: u32,
+    pub bottom_side_bearing: i32,
+    /// Vertical origin of the glyph for vertical text, from the top of the em box.
+    pub vertical_origin_y: i32,
+}
+
+impl Deref for FontFace {
+    type Target = FontFaceRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type FontFallbackRaw = ComPtr<dwrite_2::IDWriteFontFallback>;
+#[derive(Clone)]
+pub struct FontFallback(FontFallbackRaw);
+
+impl Deref for FontFallback {
+    type Target = FontFallbackRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type FontFallbackBuilderRaw = ComPtr<dwrite_2::IDWriteFontFallbackBuilder>;
+pub struct FontFallbackBuilder(FontFallbackBuilderRaw);
+
+impl FontFallbackBuilder {
+    /// Map code point ranges to font families, tried in order.
+    ///
+    /// Mappings added first take precedence, families are looked up in the
+    /// installed fonts if `collection` is `None`. `scale` adjusts the size of
+    /// the fallback fonts relative to the base font.
+    pub fn add_mapping(
+        &self,
+        ranges: &[RangeInclusive<u32>],
+        families: &[&str],
+        collection: Option<&FontCollection>,
+        locale: Option<&str>,
+        base_family: Option<&str>,
+        scale: f32,
+    ) -> Result<(), Error> {
+        let ranges = ranges
+            .iter()
+            .map(|range| dwrite_1::DWRITE_UNICODE_RANGE {
+                first: *range.start(),
+                last: *range.end(),
+            })
+            .collect::<Vec<_>>();
+        let families = families
+            .iter()
+            .map(|family| family.to_wide_null())
+            .collect::<Vec<_>>();
+        let mut family_ptrs = families
+            .iter()
+            .map(|family| family.as_ptr())
+            .collect::<Vec<_>>();
+        let collection = collection.map_or(ptr::null_mut(), |collection| collection.as_raw());
+        let locale = locale.map(|locale| locale.to_wide_null());
+        let base_family = base_family.map(|family| family.to_wide_null());
+        let hr = unsafe {
+            self.AddMapping(
+                ranges.as_ptr(),
+                ranges.len() as _,
+                family_ptrs.as_mut_ptr(),
+                family_ptrs.len() as _,
+                collection,
+                locale
+                    .as_ref()
+                    .map_or(ptr::null(), |locale| locale.as_ptr()),
+                base_family
+                    .as_ref()
+                    .map_or(ptr::null(), |family| family.as_ptr()),
+                scale,
+            )
+        };
+        Error::check(hr)
+    }
+
+    /// Append all mappings of an existing fallback, e.g. the system fallback.
+    pub fn add_mappings(&self, fallback: &FontFallback) -> Result<(), Error> {
+        Error::check(unsafe { self.AddMappings(fallback.as_raw()) })
+    }
+
+    pub fn create_font_fallback(&self) -> Result<FontFallback, Error> {
+        unsafe {
+            let mut fallback = ptr::null_mut();
+            let hr = self.CreateFontFallback(&mut fallback);
+            Error::check(hr)?;
+            Ok(FontFallback(FontFallbackRaw::from_raw(fallback)))
+        }
+    }
+}
+
+impl Deref for FontFallbackBuilder {
+    type Target = FontFallbackBuilderRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type LocalizedStringsRaw = ComPtr<dwrite::IDWriteLocalizedStrings>;
+pub struct LocalizedStrings(LocalizedStringsRaw);
+
+impl LocalizedStrings {
+    pub fn get_count(&self) -> u32 {
+        unsafe { self.GetCount() }
+    }
+
+    /// Index of the string for the locale, locale names are case-insensitive.
+    pub fn find_locale_name(&self, locale: &str) -> Option<u32> {
+        let locale = locale.to_wide_null();
+        unsafe {
+            let mut index = 0;
+            let mut exists = FALSE;
+            let hr = self.FindLocaleName(locale.as_ptr(), &mut index, &mut exists);
+            if Error::check(hr).is_ok() && exists != FALSE {
+                Some(index)
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn get_locale_name(&self, index: u32) -> Result<String, Error> {
+        unsafe {
+            let mut len = 0;
+            Error::check(self.GetLocaleNameLength(index, &mut len))?;
+            let mut locale = vec![0; len as usize + 1];
+            Error::check(self.GetLocaleName(index, locale.as_mut_ptr(), locale.len() as _))?;
+            Ok(String::from_utf16_lossy(&locale[..len as usize]))
+        }
+    }
+
+    pub fn get_string(&self, index: u32) -> Result<String, Error> {
+        unsafe {
+            let mut len = 0;
+            Error::check(self.GetStringLength(index, &mut len))?;
+            let mut string = vec![0; len as usize + 1];
+            Error::check(self.GetString(index, string.as_mut_ptr(), string.len() as _))?;
+            Ok(String::from_utf16_lossy(&string[..len as usize]))
+        }
+    }
+
+    /// String for the locale, falling back to `en-us` and then to the first string.
+    pub fn get_localized(&self, locale: &str) -> Result<String, Error> {
+        let index = self
+            .find_locale_name(locale)
+            .or_else(|| self.find_locale_name("en-us"))
+            .unwrap_or(0);
+        self.get_string(index)
+    }
+}
+
+impl Deref for LocalizedStrings {
+    type Target = LocalizedStringsRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type TextFormatRaw = ComPtr<dwrite::IDWriteTextFormat>;
+#[derive(Clone)]
+pub struct TextFormat(TextFormatRaw);
+
+impl TextFormat {
+    /// Collection the font family is looked up in, `None` for the system font collection.
+    pub fn get_font_collection(&self) -> Option<FontCollection> {
+        unsafe {
+            let mut collection = ptr::null_mut();
+            let hr = self.GetFontCollection(&mut collection);
+            if Error::check(hr).is_ok() && !collection.is_null() {
+                Some(FontCollection::from_raw(collection))
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn get_font_family_name(&self) -> String {
+        unsafe {
+            let len = self.GetFontFamilyNameLength();
+            let mut family = vec![0; len as usize + 1];
+            let _hr = self.GetFontFamilyName(family.as_mut_ptr(), family.len() as _);
+            String::from_utf16_lossy(&family[..len as usize])
+        }
+    }
+
+    pub fn get_locale_name(&self) -> String {
+        unsafe {
+            let len = self.GetLocaleNameLength();
+            let mut locale = vec![0; len as usize + 1];
+            let _hr = self.GetLocaleName(locale.as_mut_ptr(), locale.len() as _);
+            String::from_utf16_lossy(&locale[..len as usize])
+        }
+    }
+
+    pub fn get_font_weight(&self) -> FontWeight {
+        FontWeight::from_raw(unsafe { self.GetFontWeight() })
+    }
+
+    pub fn get_font_style(&self) -> FontStyle {
+        FontStyle::from_raw(unsafe { self.GetFontStyle() })
+    }
+
+    pub fn get_font_stretch(&self) -> FontStretch {
+        FontStretch::from_raw(unsafe { self.GetFontStretch() })
+    }
+
+    pub fn get_font_size(&self) -> f32 {
+        unsafe { self.GetFontSize() }
+    }
+}
+
+impl Deref for TextFormat {
+    type Target = TextFormatRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type RenderingParamsRaw = ComPtr<dwrite::IDWriteRenderingParams>;
+#[derive(Clone)]
+pub struct RenderingParams(RenderingParamsRaw);
+
+impl Deref for RenderingParams {
+    type Target = RenderingParamsRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type TypographyRaw = ComPtr<dwrite::IDWriteTypography>;
+#[derive(Clone)]
+pub struct Typography(TypographyRaw);
+
+impl Typography {
+    /// Enable a font feature, `parameter` selects the alternate for features with
+    /// multiple alternates, 0 disables the feature.
+    pub fn add_font_feature(&self, tag: FontFeatureTag, parameter: u32) -> Result<(), Error> {
+        let feature = dwrite::DWRITE_FONT_FEATURE {
+            nameTag: tag.0,
+            parameter,
+        };
+        Error::check(unsafe { self.AddFontFeature(feature) })
+    }
+}
+
+impl Deref for Typography {
+    type Target = TypographyRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub type TextAnalyzerRaw = ComPtr<dwrite::IDWriteTextAnalyzer>;
+#[derive(Clone)]
+pub struct TextAnalyzer(TextAnalyzerRaw);
+
+impl TextAnalyzer {
+    /// Resolved bidi embedding level of each UTF-16 code unit of a paragraph with the
+    /// base direction, odd levels are right-to-left.
+    pub fn analyze_bidi(
+        &self,
+        text: &[u16],
+        locale: &str,
+        direction: ReadingDirection,
+    ) -> Result<Vec<u8>, Error> {
+        let source: ComPtr<dwrite::IDWriteTextAnalysisSource> =
+            com::Object::create(AnalysisSource {
+                text: text.to_vec(),
+                locale: locale.to_wide_null(),
+                direction,
+            });
+        let sink: ComPtr<dwrite::IDWriteTextAnalysisSink> = com::Object::create(AnalysisSink {
+            levels: RefCell::new(vec![0; text.len()]),
+        });
+        unsafe {
+            let hr = self.AnalyzeBidi(source.as_raw(), 0, text.len() as _, sink.as_raw());
+            Error::check(hr)?;
+            let sink = com::Object::<AnalysisSink>::from_interface(sink.as_raw());
+            Ok(sink.levels.replace(Vec::new()))
+        }
+    }
+}
+
+impl Deref for TextAnalyzer {
+    type Target = TextAnalyzerRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// `IDWriteTextAnalysisSource` providing a single paragraph of text.
+struct AnalysisSource {
+    text: Vec<u16>,
+    /// Null terminated locale name, used for the whole text.
+    locale: Vec<u16>,
+    direction: ReadingDirection,
+}
+
+unsafe impl com::Class for AnalysisSource {
+    type Vtbl = dwrite::IDWriteTextAnalysisSourceVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteTextAnalysisSourceVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        GetTextAtPosition: Self::get_text_at_position,
+        GetTextBeforePosition: Self::get_text_before_position,
+        GetParagraphReadingDirection: Self::get_paragraph_reading_direction,
+        GetLocaleName: Self::get_locale_name,
+        GetNumberSubstitution: Self::get_number_substitution,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteTextAnalysisSource::uuidof())
+    }
+}
+
+impl AnalysisSource {
+    /// Length of the text from the position to the end, 0 past the end.
+    fn remaining(&self, position: u32) -> u32 {
+        (self.text.len() as u32).saturating_sub(position)
+    }
+
+    unsafe extern "system" fn get_text_at_position(
+        this: *mut dwrite::IDWriteTextAnalysisSource,
+        position: u32,
+        text: *mut *const u16,
+        length: *mut u32,
+    ) -> HRESULT {
+        let source = com::Object::<Self>::from_interface(this);
+        *length = source.remaining(position);
+        *text = if *length > 0 {
+            source.text[position as usize..].as_ptr()
+        } else {
+            ptr::null()
+        };
+        S_OK
+    }
+
+    unsafe extern "system" fn get_text_before_position(
+        this: *mut dwrite::IDWriteTextAnalysisSource,
+        position: u32,
+        text: *mut *const u16,
+        length: *mut u32,
+    ) -> HRESULT {
+        let source = com::Object::<Self>::from_interface(this);
+        if position == 0 || position as usize > source.text.len() {
+            *text = ptr::null();
+            *length = 0;
+        } else {
+            *text = source.text.as_ptr();
+            *length = position;
+        }
+        S_OK
+    }
+
+    unsafe extern "system" fn get_paragraph_reading_direction(
+        this: *mut dwrite::IDWriteTextAnalysisSource,
+    ) -> dwrite::DWRITE_READING_DIRECTION {
+        com::Object::<Self>::from_interface(this).direction as _
+    }
+
+    unsafe extern "system" fn get_locale_name(
+        this: *mut dwrite::IDWriteTextAnalysisSource,
+        position: u32,
+        length: *mut u32,
+        locale: *mut *const u16,
+    ) -> HRESULT {
+        let source = com::Object::<Self>::from_interface(this);
+        *length = source.remaining(position);
+        *locale = source.locale.as_ptr();
+        S_OK
+    }
+
+    unsafe extern "system" fn get_number_substitution(
+        this: *mut dwrite::IDWriteTextAnalysisSource,
+        position: u32,
+        length: *mut u32,
+        substitution: *mut *mut dwrite::IDWriteNumberSubstitution,
+    ) -> HRESULT {
+        let source = com::Object::<Self>::from_interface(this);
+        *length = source.remaining(position);
+        *substitution = ptr::null_mut();
+        S_OK
+    }
+}
+
+/// `IDWriteTextAnalysisSink` recording the resolved bidi levels.
+struct AnalysisSink {
+    levels: RefCell<Vec<u8>>,
+}
+
+unsafe impl com::Class for AnalysisSink {
+    type Vtbl = dwrite::IDWriteTextAnalysisSinkVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteTextAnalysisSinkVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        SetScriptAnalysis: Self::set_script_analysis,
+        SetLineBreakpoints: Self::set_line_breakpoints,
+        SetBidiLevel: Self::set_bidi_level,
+        SetNumberSubstitution: Self::set_number_substitution,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteTextAnalysisSink::uuidof())
+    }
+}
+
+impl AnalysisSink {
+    unsafe extern "system" fn set_script_analysis(
+        _this: *mut dwrite::IDWriteTextAnalysisSink,
+        _position: u32,
+        _length: u32,
+        _analysis: *const dwrite::DWRITE_SCRIPT_ANALYSIS,
+    ) -> HRESULT {
+        S_OK
+    }
+
+    unsafe extern "system" fn set_line_breakpoints(
+        _this: *mut dwrite::IDWriteTextAnalysisSink,
+        _position: u32,
+        _length: u32,
+        _breakpoints: *const dwrite::DWRITE_LINE_BREAKPOINT,
+    ) -> HRESULT {
+        S_OK
+    }
+
+    unsafe extern "system" fn set_bidi_level(
+        this: *mut dwrite::IDWriteTextAnalysisSink,
+        position: u32,
+        length: u32,
+        _explicit_level: u8,
+        resolved_level: u8,
+    ) -> HRESULT {
+        let sink = com::Object::<Self>::from_interface(this);
+        let mut levels = sink.levels.borrow_mut();
+        let start = (position as usize).min(levels.len());
+        let end = (position as usize + length as usize).min(levels.len());
+        for level in &mut levels[start..end] {
+            *level = resolved_level;
+        }
+        S_OK
+    }
+
+    unsafe extern "system" fn set_number_substitution(
+        _this: *mut dwrite::IDWriteTextAnalysisSink,
+        _position: u32,
+        _length: u32,
+        _substitution: *mut dwrite::IDWriteNumberSubstitution,
+    ) -> HRESULT {
+        S_OK
+    }
+}
+
+pub type InlineObjectRaw = ComPtr<dwrite::IDWriteInlineObject>;
+#[derive(Clone)]
+pub struct InlineObject(InlineObjectRaw);
+
+impl InlineObject {
+    /// Inline object implemented by `object`, drawn by Direct2D or passed to custom text renderers.
+    pub fn new(object: impl CustomInlineObject + 'static) -> Self {
+        InlineObject(com::Object::create(InlineObjectBridge(Box::new(object))))
+    }
+
+    /// Draw the object, objects drawing text like trimming signs call back into the renderer.
+    pub fn draw(
+        &self,
+        mut renderer: &mut dyn TextRenderer,
+        origin: [f32; 2],
+        is_sideways: bool,
+        is_right_to_left: bool,
+        effect: Option<DrawingEffect>,
+    ) -> Result<(), Error> {
+        let bridge: ComPtr<dwrite::IDWriteTextRenderer> = com::Object::create(RendererBridge);
+        let context = &mut renderer as *mut &mut dyn TextRenderer as *mut c_void;
+        let effect = effect.map_or(ptr::null_mut(), |effect| effect.0 as *const _ as *mut _);
+        Error::check(unsafe {
+            self.Draw(
+                context,
+                bridge.as_raw(),
+                origin[0],
+                origin[1],
+                is_sideways as _,
+                is_right_to_left as _,
+                effect,
+            )
+        })
+    }
+}
+
+impl Deref for InlineObject {
+    type Target = InlineObjectRaw;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Size of an inline object in DIPs.
+#[derive(Copy, Clone, Debug)]
+pub struct InlineObjectMetrics {
+    pub width: f32,
+    pub height: f32,
+    /// Distance from the top of the object to its baseline.
+    pub baseline: f32,
+    /// Whether the object is rotated with the glyphs in vertical text.
+    pub supports_sideways: bool,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BreakCondition {
+    /// Use the break condition of the neighbouring text.
+    Neutral = dwrite::DWRITE_BREAK_CONDITION_NEUTRAL,
+    CanBreak = dwrite::DWRITE_BREAK_CONDITION_CAN_BREAK,
+    MayNotBreak = dwrite::DWRITE_BREAK_CONDITION_MAY_NOT_BREAK,
+    MustBreak = dwrite::DWRITE_BREAK_CONDITION_MUST_BREAK,
+}
+
+/// Object placed inline in a text layout, replacing the text of its range.
+pub trait CustomInlineObject {
+    fn metrics(&self) -> InlineObjectMetrics;
+
+    /// Extent of the drawing outside of the object box, positive values are outside.
+    fn overhang_metrics(&self) -> OverhangMetrics {
+        OverhangMetrics::default()
+    }
+
+    /// Line break conditions before and after the object.
+    fn break_conditions(&self) -> (BreakCondition, BreakCondition) {
+        (BreakCondition::Neutral, BreakCondition::Neutral)
+    }
+
+    /// `origin` is the top-left corner of the object.
+    fn draw(&self, origin: [f32; 2], is_sideways: bool, is_right_to_left: bool);
+}
+
+/// `IDWriteInlineObject` forwarding to a `CustomInlineObject`.
+struct InlineObjectBridge(Box<dyn CustomInlineObject>);
+
+unsafe impl com::Class for InlineObjectBridge {
+    type Vtbl = dwrite::IDWriteInlineObjectVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteInlineObjectVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        Draw: Self::draw,
+        GetMetrics: Self::get_metrics,
+        GetOverhangMetrics: Self::get_overhang_metrics,
+        GetBreakConditions: Self::get_break_conditions,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteInlineObject::uuidof())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+impl InlineObjectBridge {
+    unsafe extern "system" fn draw(
+        this: *mut dwrite::IDWriteInlineObject,
+        _context: *mut c_void,
+        _renderer: *mut dwrite::IDWriteTextRenderer,
+        origin_x: f32,
+        origin_y: f32,
+        is_sideways: BOOL,
+        is_right_to_left: BOOL,
+        _effect: *mut IUnknown,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let object = com::Object::<Self>::from_interface(this);
+            object.0.draw(
+                [origin_x, origin_y],
+                is_sideways != FALSE,
+                is_right_to_left != FALSE,
+            );
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn get_metrics(
+        this: *mut dwrite::IDWriteInlineObject,
+        metrics: *mut dwrite::DWRITE_INLINE_OBJECT_METRICS,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let object = com::Object::<Self>::from_interface(this);
+            let m = object.0.metrics();
+            *metrics = dwrite::DWRITE_INLINE_OBJECT_METRICS {
+                width: m.width,
+                height: m.height,
+                baseline: m.baseline,
+                supportsSideways: m.supports_sideways as _,
+            };
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn get_overhang_metrics(
+        this: *mut dwrite::IDWriteInlineObject,
+        overhangs: *mut dwrite::DWRITE_OVERHANG_METRICS,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let object = com::Object::<Self>::from_interface(this);
+            let m = object.0.overhang_metrics();
+            *overhangs = dwrite::DWRITE_OVERHANG_METRICS {
+                left: m.left,
+                top: m.top,
+                right: m.right,
+                bottom: m.bottom,
+            };
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn get_break_conditions(
+        this: *mut dwrite::IDWriteInlineObject,
+        before: *mut dwrite::DWRITE_BREAK_CONDITION,
+        after: *mut dwrite::DWRITE_BREAK_CONDITION,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let object = com::Object::<Self>::from_interface(this);
+            let (condition_before, condition_after) = object.0.break_conditions();
+            *before = condition_before as _;
+            *after = condition_after as _;
+            S_OK
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OverhangMetrics {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TextMetrics {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub width_incl_trailing_whitespaces: f32,
+    pub height: f32,
+    pub layout_width: f32,
+    pub layout_height: f32,
+    pub max_bidi_reordering_depth: u32,
+    pub line_count: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LineMetrics {
+    /// Number of UTF-16 code units in the line, including trailing whitespace and newline.
+    pub length: u32,
+    pub trailing_whitespace_length: u32,
+    pub newline_length: u32,
+    pub height: f32,
+    /// Distance from the top of the line to its baseline.
+    pub baseline: f32,
+    pub is_trimmed: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ClusterMetrics {
+    pub width: f32,
+    /// Number of UTF-16 code units in the cluster.
+    pub length: u16,
+    pub can_wrap_line_after: bool,
+    pub is_whitespace: bool,
+    pub is_newline: bool,
+    pub is_soft_hyphen: bool,
+    pub is_right_to_left: bool,
+}
+
+/// Geometry of a text range, positions and lengths are in UTF-16 code units.
+#[derive(Copy, Clone, Debug)]
+pub struct HitTestMetrics {
+    pub text_position: u32,
+    pub length: u32,
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub bidi_level: u32,
+    pub is_text: bool,
+    pub is_trimmed: bool,
+}
+
+impl From<dwrite::DWRITE_HIT_TEST_METRICS> for HitTestMetrics {
+    fn from(metrics: dwrite::DWRITE_HIT_TEST_METRICS) -> Self {
+        HitTestMetrics {
+            text_position: metrics.textPosition,
+            length: metrics.length,
+            left: metrics.left,
+            top: metrics.top,
+            width: metrics.width,
+            height: metrics.height,
+            bidi_level: metrics.bidiLevel,
+            is_text: metrics.isText != 0,
+            is_trimmed: metrics.isTrimmed != 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct HitTestPoint {
+    pub is_trailing_hit: bool,
+    pub is_inside: bool,
+    pub metrics: HitTestMetrics,
+}
+
+fn text_range(range: Range<u32>) -> dwrite::DWRITE_TEXT_RANGE {
+    dwrite::DWRITE_TEXT_RANGE {
+        startPosition: range.start,
+        length: range.end.saturating_sub(range.start),
+    }
+}
+
+/// Offset of a glyph from its position given by the advances.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GlyphOffset {
+    /// Offset in reading direction.
+    pub advance_offset: f32,
+    /// Offset perpendicular to the baseline, positive values move the glyph up.
+    pub ascender_offset: f32,
+}
+
+/// Opaque reference to the font face of a glyph run, equal for the same face.
+#[derive(Copy, Clone)]
+pub struct FontFaceHandle<'a>(&'a FontFace);
+
+impl<'a> FontFaceHandle<'a> {
+    /// DirectWrite font face, e.g. for querying glyph metrics.
+    pub fn font_face(&self) -> &'a FontFace {
+        self.0
+    }
+}
+
+impl<'a> From<&'a FontFace> for FontFaceHandle<'a> {
+    fn from(font_face: &'a FontFace) -> Self {
+        FontFaceHandle(font_face)
+    }
+}
+
+impl PartialEq for FontFaceHandle<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_raw() == other.0.as_raw()
+    }
+}
+
+impl Eq for FontFaceHandle<'_> {}
+
+impl Hash for FontFaceHandle<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_raw().hash(state);
+    }
+}
+
+impl fmt::Debug for FontFaceHandle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("FontFaceHandle")
+            .field(&self.0.as_raw())
+            .finish()
+    }
+}
+
+/// Glyphs of a single font face and size placed along a baseline.
+#[derive(Copy, Clone, Debug)]
+pub struct GlyphRun<'a> {
+    pub font_face: FontFaceHandle<'a>,
+    pub font_size: f32,
+    pub glyph_indices: &'a [u16],
+    /// Advance of each glyph in reading direction, `None` to use the advances of the font.
+    ///
+    /// Must have the same length as `glyph_indices`.
+    pub glyph_advances: Option<&'a [f32]>,
+    /// Offset of each glyph, `None` if no glyph is offset.
+    ///
+    /// Must have the same length as `glyph_indices`.
+    pub glyph_offsets: Option<&'a [GlyphOffset]>,
+    /// Whether the glyphs are rotated 90 degrees, used for vertical text.
+    pub is_sideways: bool,
+    /// Bidi level of the run, odd levels are right-to-left.
+    pub bidi_level: u32,
+}
+
+impl GlyphRun<'_> {
+    /// Outlines of the glyphs in DIPs, relative to the baseline origin.
+    pub fn get_outline(&self) -> Result<Vec<OutlineElement>, Error> {
+        let sink: ComPtr<d2d1::ID2D1SimplifiedGeometrySink> = com::Object::create(OutlineSink {
+            elements: RefCell::new(Vec::new()),
+        });
+        let run = self.as_raw();
+        unsafe {
+            let hr = self.font_face.font_face().GetGlyphRunOutline(
+                run.fontEmSize,
+                run.glyphIndices,
+                run.glyphAdvances,
+                run.glyphOffsets,
+                run.glyphCount,
+                run.isSideways,
+                (run.bidiLevel % 2 == 1) as _,
+                sink.as_raw(),
+            );
+            Error::check(hr)?;
+            let sink = com::Object::<OutlineSink>::from_interface(sink.as_raw());
+            Ok(sink.elements.replace(Vec::new()))
+        }
+    }
+
+    /// Raw glyph run borrowing the slices.
+    ///
+    /// Panics if the advances or offsets don't have one entry per glyph,
+    /// DirectWrite would read past the end of the slices otherwise.
+    pub(crate) fn as_raw(&self) -> dwrite::DWRITE_GLYPH_RUN {
+        let count = self.glyph_indices.len();
+        if let Some(advances) = self.glyph_advances {
+            assert_eq!(advances.len(), count, "glyph advance count mismatch");
+        }
+        if let Some(offsets) = self.glyph_offsets {
+            assert_eq!(offsets.len(), count, "glyph offset count mismatch");
+        }
+        dwrite::DWRITE_GLYPH_RUN {
+            fontFace: self.font_face.font_face().as_raw(),
+            fontEmSize: self.font_size,
+            glyphCount: count as _,
+            glyphIndices: self.glyph_indices.as_ptr(),
+            glyphAdvances: self
+                .glyph_advances
+                .map_or(ptr::null(), |advances| advances.as_ptr()),
+            glyphOffsets: self
+                .glyph_offsets
+                .map_or(ptr::null(), |offsets| offsets.as_ptr() as *const _),
+            isSideways: self.is_sideways as _,
+            bidiLevel: self.bidi_level,
+        }
+    }
+}
+
+/// Element of a glyph outline, y points down.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutlineElement {
+    BeginFigure([f32; 2]),
+    Line([f32; 2]),
+    /// Cubic bezier segment with two control points and the end point.
+    Bezier([f32; 2], [f32; 2], [f32; 2]),
+    EndFigure {
+        closed: bool,
+    },
+}
+
+/// `ID2D1SimplifiedGeometrySink` recording the outline elements.
+struct OutlineSink {
+    elements: RefCell<Vec<OutlineElement>>,
+}
+
+unsafe impl com::Class for OutlineSink {
+    type Vtbl = d2d1::ID2D1SimplifiedGeometrySinkVtbl;
+    const VTBL: &'static Self::Vtbl = &d2d1::ID2D1SimplifiedGeometrySinkVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: com::query_interface::<Self>,
+            AddRef: com::add_ref::<Self>,
+            Release: com::release::<Self>,
+        },
+        SetFillMode: Self::set_fill_mode,
+        SetSegmentFlags: Self::set_segment_flags,
+        BeginFigure: Self::begin_figure,
+        AddLines: Self::add_lines,
+        AddBeziers: Self::add_beziers,
+        EndFigure: Self::end_figure,
+        Close: Self::close,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &d2d1::ID2D1SimplifiedGeometrySink::uuidof())
+    }
+}
+
+impl OutlineSink {
+    unsafe fn push(
+        this: *mut d2d1::ID2D1SimplifiedGeometrySink,
+        elements: impl IntoIterator<Item = OutlineElement>,
+    ) {
+        let sink = com::Object::<Self>::from_interface(this);
+        sink.elements.borrow_mut().extend(elements);
+    }
+
+    unsafe extern "system" fn set_fill_mode(
+        _this: *mut d2d1::ID2D1SimplifiedGeometrySink,
+        _mode: d2d1::D2D1_FILL_MODE,
+    ) {
+    }
+
+    unsafe extern "system" fn set_segment_flags(
+        _this: *mut d2d1::ID2D1SimplifiedGeometrySink,
+        _flags: d2d1::D2D1_PATH_SEGMENT,
+    ) {
+    }
+
+    unsafe extern "system" fn begin_figure(
+        this: *mut d2d1::ID2D1SimplifiedGeometrySink,
+        start: d2d1::D2D1_POINT_2F,
+        _begin: d2d1::D2D1_FIGURE_BEGIN,
+    ) {
+        Self::push(this, Some(OutlineElement::BeginFigure([start.x, start.y])));
+    }
+
+    unsafe extern "system" fn add_lines(
+        this: *mut d2d1::ID2D1SimplifiedGeometrySink,
+        points: *const d2d1::D2D1_POINT_2F,
+        count: u32,
+    ) {
+        let points = slice_from_raw(points, count);
+        Self::push(
+            this,
+            points.iter().map(|p| OutlineElement::Line([p.x, p.y])),
+        );
+    }
+
+    unsafe extern "system" fn add_beziers(
+        this: *mut d2d1::ID2D1SimplifiedGeometrySink,
+        beziers: *const d2d1::D2D1_BEZIER_SEGMENT,
+        count: u32,
+    ) {
+        let beziers = slice_from_raw(beziers, count);
+        Self::push(
+            this,
+            beziers.iter().map(|b| {
+                OutlineElement::Bezier(
+                    [b.point1.x, b.point1.y],
+                    [b.point2.x, b.point2.y],
+                    [b.point3.x, b.point3.y],
+                )
+            }),
+        );
+    }
+
+    unsafe extern "system" fn end_figure(
+        this: *mut d2d1::ID2D1SimplifiedGeometrySink,
+        end: d2d1::D2D1_FIGURE_END,
+    ) {
+        let closed = end == d2d1::D2D1_FIGURE_END_CLOSED;
+        Self::push(this, Some(OutlineElement::EndFigure { closed }));
+    }
+
+    unsafe extern "system" fn close(_this: *mut d2d1::ID2D1SimplifiedGeometrySink) -> HRESULT {
+        S_OK
+    }
+}
+
+/// Text of a glyph run, positions are in UTF-16 code units.
+#[derive(Copy, Clone, Debug)]
+pub struct GlyphRunDescription<'a> {
+    pub text: &'a [u16],
+    /// Index of the first glyph of the cluster for each code unit of the text.
+    pub cluster_map: &'a [u16],
+    /// Position of the text in the layout.
+    pub text_position: u32,
+}
+
+/// Drawing effect of a text range, set with `TextLayout::set_drawing_effect`.
+#[derive(Copy, Clone)]
+pub struct DrawingEffect<'a>(&'a IUnknown);
+
+impl DrawingEffect<'_> {
+    /// Whether the effect is the brush.
+    pub fn is_brush(&self, brush: &impl Brush) -> bool {
+        self.0 as *const IUnknown as usize == brush.as_brush() as usize
+    }
+}
+
+/// Underline or strikethrough line, starting at the baseline origin.
+#[derive(Copy, Clone, Debug)]
+pub struct Decoration {
+    pub width: f32,
+    pub thickness: f32,
+    /// Offset of the top of the line from the baseline, positive values are below the baseline.
+    pub offset: f32,
+    /// Height of the tallest run the line is drawn under, zero for strikethroughs.
+    pub run_height: f32,
+}
+
+/// Receives the glyph runs, lines and inline objects of a DirectWrite text layout.
+///
+/// Positions are in DIPs relative to the origin the layout is drawn at. Text layouts of the
+/// crate are drawn with `text::TextRenderer` instead.
+pub trait TextRenderer {
+    fn draw_glyph_run(
+        &mut self,
+        baseline_origin: [f32; 2],
+        measuring_mode: MeasuringMode,
+        glyph_run: &GlyphRun,
+        description: &GlyphRunDescription,
+        effect: Option<DrawingEffect>,
+    );
+
+    fn draw_underline(
+        &mut self,
+        _baseline_origin: [f32; 2],
+        _underline: &Decoration,
+        _effect: Option<DrawingEffect>,
+    ) {
+    }
+
+    fn draw_strikethrough(
+        &mut self,
+        _baseline_origin: [f32; 2],
+        _strikethrough: &Decoration,
+        _effect: Option<DrawingEffect>,
+    ) {
+    }
+
+    /// `origin` is the top-left corner of the inline object.
+    fn draw_inline_object(
+        &mut self,
+        _origin: [f32; 2],
+        _object: &InlineObject,
+        _is_sideways: bool,
+        _is_right_to_left: bool,
+        _effect: Option<DrawingEffect>,
+    ) {
+    }
+
+    fn is_pixel_snapping_disabled(&self) -> bool {
+        false
+    }
+
+    /// Transform from DIPs to pixels as `[m11, m12, m21, m22, dx, dy]`, used for pixel snapping.
+    fn current_transform(&self) -> [f32; 6] {
+        [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+    }
+
+    fn pixels_per_dip(&self) -> f32 {
+        1.0
+    }
+}
+
+/// Slice from a pointer which may be null for empty slices.
+unsafe fn slice_from_raw<'a, T>(data: *const T, len: u32) -> &'a [T] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(data, len as _)
+    }
+}
+
+/// Slice from a pointer which is null if the data is omitted.
+unsafe fn optional_slice_from_raw<'a, T>(data: *const T, len: u32) -> Option<&'a [T]> {
+    if data.is_null() {
+        None
+    } else {
+        Some(slice_from_raw(data, len))
+    }
+}
+
+/// Run a callback into user code, panics must not unwind into DirectWrite.
+fn catch_panic(f: impl FnOnce() -> HRESULT) -> HRESULT {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(E_FAIL)
+}
+
+/// `IDWriteTextRenderer` forwarding to the `TextRenderer` passed as drawing context.
+struct RendererBridge;
+
+#[allow(clippy::too_many_arguments)]
+impl RendererBridge {
+    unsafe fn renderer<'a>(context: *mut c_void) -> &'a mut dyn TextRenderer {
+        &mut **(context as *mut &mut dyn TextRenderer)
+    }
+
+    unsafe fn effect<'a>(effect: *mut IUnknown) -> Option<DrawingEffect<'a>> {
+        effect.as_ref().map(DrawingEffect)
+    }
+
+    unsafe extern "system" fn is_pixel_snapping_disabled(
+        _this: *mut dwrite::IDWritePixelSnapping,
+        context: *mut c_void,
+        is_disabled: *mut BOOL,
+    ) -> HRESULT {
+        catch_panic(|| {
+            *is_disabled = Self::renderer(context).is_pixel_snapping_disabled() as _;
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn get_current_transform(
+        _this: *mut dwrite::IDWritePixelSnapping,
+        context: *mut c_void,
+        transform: *mut dwrite::DWRITE_MATRIX,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let [m11, m12, m21, m22, dx, dy] = Self::renderer(context).current_transform();
+            *transform = dwrite::DWRITE_MATRIX {
+                m11,
+                m12,
+                m21,
+                m22,
+                dx,
+                dy,
+            };
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn get_pixels_per_dip(
+        _this: *mut dwrite::IDWritePixelSnapping,
+        context: *mut c_void,
+        pixels_per_dip: *mut f32,
+    ) -> HRESULT {
+        catch_panic(|| {
+            *pixels_per_dip = Self::renderer(context).pixels_per_dip();
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn draw_glyph_run(
+        _this: *mut dwrite::IDWriteTextRenderer,
+        context: *mut c_void,
+        baseline_origin_x: f32,
+        baseline_origin_y: f32,
+        measuring_mode: dcommon::DWRITE_MEASURING_MODE,
+        glyph_run: *const dwrite::DWRITE_GLYPH_RUN,
+        description: *const dwrite::DWRITE_GLYPH_RUN_DESCRIPTION,
+        effect: *mut IUnknown,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let run = &*glyph_run;
+            (*run.fontFace).AddRef();
+            let font_face = FontFace(FontFaceRaw::from_raw(run.fontFace));
+            let glyph_offsets = run.glyphOffsets as *const GlyphOffset;
+            let glyph_run = GlyphRun {
+                font_face: FontFaceHandle(&font_face),
+                font_size: run.fontEmSize,
+                glyph_indices: slice_from_raw(run.glyphIndices, run.glyphCount),
+                glyph_advances: optional_slice_from_raw(run.glyphAdvances, run.glyphCount),
+                glyph_offsets: optional_slice_from_raw(glyph_offsets, run.glyphCount),
+                is_sideways: run.isSideways != FALSE,
+                bidi_level: run.bidiLevel,
+            };
+            let description = if description.is_null() {
+                GlyphRunDescription {
+                    text: &[],
+                    cluster_map: &[],
+                    text_position: 0,
+                }
+            } else {
+                let description = &*description;
+                GlyphRunDescription {
+                    text: slice_from_raw(description.string, description.stringLength),
+                    cluster_map: slice_from_raw(description.clusterMap, description.stringLength),
+                    text_position: description.textPosition,
+                }
+            };
+            Self::renderer(context).draw_glyph_run(
+                [baseline_origin_x, baseline_origin_y],
+                MeasuringMode::from_raw(measuring_mode),
+                &glyph_run,
+                &description,
+                Self::effect(effect),
+            );
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn draw_underline(
+        _this: *mut dwrite::IDWriteTextRenderer,
+        context: *mut c_void,
+        baseline_origin_x: f32,
+        baseline_origin_y: f32,
+        underline: *const dwrite::DWRITE_UNDERLINE,
+        effect: *mut IUnknown,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let underline = &*underline;
+            Self::renderer(context).draw_underline(
+                [baseline_origin_x, baseline_origin_y],
+                &Decoration {
+                    width: underline.width,
+                    thickness: underline.thickness,
+                    offset: underline.offset,
+                    run_height: underline.runHeight,
+                },
+                Self::effect(effect),
+            );
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn draw_strikethrough(
+        _this: *mut dwrite::IDWriteTextRenderer,
+        context: *mut c_void,
+        baseline_origin_x: f32,
+        baseline_origin_y: f32,
+        strikethrough: *const dwrite::DWRITE_STRIKETHROUGH,
+        effect: *mut IUnknown,
+    ) -> HRESULT {
+        catch_panic(|| {
+            let strikethrough = &*strikethrough;
+            Self::renderer(context).draw_strikethrough(
+                [baseline_origin_x, baseline_origin_y],
+                &Decoration {
+                    width: strikethrough.width,
+                    thickness: strikethrough.thickness,
+                    offset: strikethrough.offset,
+                    run_height: 0.0,
+                },
+                Self::effect(effect),
+            );
+            S_OK
+        })
+    }
+
+    unsafe extern "system" fn draw_inline_object(
+        _this: *mut dwrite::IDWriteTextRenderer,
+        context: *mut c_void,
+        origin_x: f32,
+        origin_y: f32,
+        object: *mut dwrite::IDWriteInlineObject,
+        is_sideways: BOOL,
+        is_right_to_left: BOOL,
+        effect: *mut IUnknown,
+    ) -> HRESULT {
+        catch_panic(|| {
+            (*object).AddRef();
+            let object = InlineObject(InlineObjectRaw::from_raw(object));
+            Self::renderer(context).draw_inline_object(
+                [origin_x, origin_y],
+                &object,
+                is_sideways != FALSE,
+                is_right_to_left != FALSE,
+                Self::effect(effect),
+            );
+            S_OK
+        })
+    }
+}
+
+unsafe impl com::Class for RendererBridge {
+    type Vtbl = dwrite::IDWriteTextRendererVtbl;
+    const VTBL: &'static Self::Vtbl = &dwrite::IDWriteTextRendererVtbl {
+        parent: dwrite::IDWritePixelSnappingVtbl {
+            parent: IUnknownVtbl {
+                QueryInterface: com::query_interface::<Self>,
+                AddRef: com::add_ref::<Self>,
+                Release: com::release::<Self>,
+            },
+            IsPixelSnappingDisabled: Self::is_pixel_snapping_disabled,
+            GetCurrentTransform: Self::get_current_transform,
+            GetPixelsPerDip: Self::get_pixels_per_dip,
+        },
+        DrawGlyphRun: Self::draw_glyph_run,
+        DrawUnderline: Self::draw_underline,
+        DrawStrikethrough: Self::draw_strikethrough,
+        DrawInlineObject: Self::draw_inline_object,
+    };
+
+    fn supports(iid: &GUID) -> bool {
+        IsEqualGUID(iid, &dwrite::IDWriteTextRenderer::uuidof())
+            || IsEqualGUID(iid, &dwrite::IDWritePixelSnapping::uuidof())
+    }
+}
+
+pub type TextLayoutRaw = ComPtr<dwrite::IDWriteTextLayout>;
+pub struct TextLayout(TextLayoutRaw);
+
+impl TextLayout {
+    pub fn set_max_width(&self, width: f32) -> Result<(), Error> {
+        Error::check(unsafe { self.SetMaxWidth(width) })
+    }
+
+    pub fn set_max_height(&self, height: f32) -> Result<(), Error> {
+        Error::check(unsafe { self.SetMaxHeight(height) })
+    }
+
+    pub fn set_word_wrapping(&self, wrapping: WordWrapping) -> Result<(), Error> {
+        Error::check(unsafe { self.SetWordWrapping(wrapping as _) })
+    }
+
+    pub fn set_text_alignment(&self, alignment: TextAlignment) -> Result<(), Error> {
+        Error::check(unsafe { self.SetTextAlignment(alignment as _) })
+    }
+
+    pub fn set_paragraph_alignment(&self, alignment: ParagraphAlignment) -> Result<(), Error> {
+        Error::check(unsafe { self.SetParagraphAlignment(alignment as _) })
+    }
+
+    /// Base direction of the paragraphs, mixed-direction text inside is
+    /// reordered by the bidi algorithm.
+    pub fn set_reading_direction(&self, direction: ReadingDirection) -> Result<(), Error> {
+        Error::check(unsafe { self.SetReadingDirection(direction as _) })
+    }
+
+    pub fn set_flow_direction(&self, direction: FlowDirection) -> Result<(), Error> {
+        Error::check(unsafe { self.SetFlowDirection(direction as _) })
+    }
+
+    /// Set the height of the lines and the distance from the top of a line to its baseline.
+    ///
+    /// Both values are ignored for `LineSpacingMethod::Default`.
+    pub fn set_line_spacing(
+        &self,
+        method: LineSpacingMethod,
+        line_spacing: f32,
+        baseline: f32,
+    ) -> Result<(), Error> {
+        Error::check(unsafe { self.SetLineSpacing(method as _, line_spacing, baseline) })
+    }
+
+    /// Trim text overflowing the layout box, optionally displaying the sign at the cut.
+    pub fn set_trimming(
+        &self,
+        granularity: TrimmingGranularity,
+        sign: Option<&InlineObject>,
+    ) -> Result<(), Error> {
+        let trimming = dwrite::DWRITE_TRIMMING {
+            granularity: granularity as _,
+            delimiter: 0,
+            delimiterCount: 0,
+        };
+        let sign = sign.map_or(ptr::null_mut(), |sign| sign.as_raw());
+        Error::check(unsafe { self.SetTrimming(&trimming, sign) })
+    }
+
+    /// Fallback for characters missing in the fonts of the layout, requires Windows 8.1.
+    pub fn set_font_fallback(&self, fallback: &FontFallback) -> Result<(), Error> {
+        let layout = self
+            .cast::<dwrite_2::IDWriteTextLayout2>()
+            .map_err(Error::Hresult)?;
+        Error::check(unsafe { layout.SetFontFallback(fallback.as_raw()) })
+    }
+
+    /// Replace the text range with the inline object.
+    pub fn set_inline_object(&self, object: &InlineObject, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetInlineObject(object.as_raw(), text_range(range)) })
+    }
+
+    pub fn set_font_family_name(&self, family: &str, range: Range<u32>) -> Result<(), Error> {
+        let family = family.to_wide_null();
+        Error::check(unsafe { self.SetFontFamilyName(family.as_ptr(), text_range(range)) })
+    }
+
+    pub fn set_font_size(&self, size: f32, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetFontSize(size, text_range(range)) })
+    }
+
+    pub fn set_font_weight(&self, weight: FontWeight, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetFontWeight(weight as _, text_range(range)) })
+    }
+
+    pub fn set_font_style(&self, style: FontStyle, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetFontStyle(style as _, text_range(range)) })
+    }
+
+    pub fn set_font_stretch(&self, stretch: FontStretch, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetFontStretch(stretch as _, text_range(range)) })
+    }
+
+    pub fn set_underline(&self, underline: bool, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetUnderline(underline as _, text_range(range)) })
+    }
+
+    pub fn set_strikethrough(&self, strikethrough: bool, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetStrikethrough(strikethrough as _, text_range(range)) })
+    }
+
+    pub fn set_typography(&self, typography: &Typography, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe { self.SetTypography(typography.as_raw(), text_range(range)) })
+    }
+
+    /// Set the brush used by Direct2D when drawing the text range.
+    ///
+    /// The layout keeps a reference to the brush.
+    pub fn set_drawing_effect(&self, brush: &impl Brush, range: Range<u32>) -> Result<(), Error> {
+        Error::check(unsafe {
+            self.SetDrawingEffect(brush.as_brush() as *mut _, text_range(range))
+        })
+    }
+
+    /// Draw the layout with a custom renderer, `origin` is the top-left corner of the layout box.
+    pub fn draw(&self, mut renderer: &mut dyn TextRenderer, origin: [f32; 2]) -> Result<(), Error> {
+        let bridge: ComPtr<dwrite::IDWriteTextRenderer> = com::Object::create(RendererBridge);
+        let context = &mut renderer as *mut &mut dyn TextRenderer as *mut c_void;
+        Error::check(unsafe { self.Draw(context, bridge.as_raw(), origin[0], origin[1]) })
+    }
+
+    pub fn get_metrics(&self) -> TextMetrics {
+        unsafe {
+            let mut metrics = mem::zeroed();
+            let _hr = self.GetMetrics(&mut metrics);
+            TextMetrics {
+                left: metrics.left,
+                top: metrics.top,
+                width: metrics.width,
+                width_incl_trailing_whitespaces: metrics.widthIncludingTrailingWhitespace,
+                height: metrics.height,
+                layout_width: metrics.layoutWidth,
+                layout_height: metrics.layoutHeight,
+                max_bidi_reordering_depth: metrics.maxBidiReorderingDepth,
+                line_count: metrics.lineCount,
+            }
+        }
+    }
+
+    pub fn get_line_metrics(&self) -> Vec<LineMetrics> {
+        let line_count = self.get_metrics().line_count;
+        let mut metrics = Vec::with_capacity(line_count as _);
+        unsafe {
+            let mut actual_count = 0;
+            let _hr = self.GetLineMetrics(metrics.as_mut_ptr(), line_count, &mut actual_count);
+            metrics.set_len(actual_count.min(line_count) as _);
+        }
+
+        metrics
+            .into_iter()
+            .map(|metrics: dwrite::DWRITE_LINE_METRICS| LineMetrics {
+                length: metrics.length,
+                trailing_whitespace_length: metrics.trailingWhitespaceLength,
+                newline_length: metrics.newlineLength,
+                height: metrics.height,
+                baseline: metrics.baseline,
+                is_trimmed: metrics.isTrimmed != 0,
+            })
+            .collect()
+    }
+
+    pub fn get_cluster_metrics(&self) -> Vec<ClusterMetrics> {
+        let mut metrics = Vec::new();
+        unsafe {
+            // Query the number of clusters first.
+            let mut cluster_count = 0;
+            let _hr = self.GetClusterMetrics(ptr::null_mut(), 0, &mut cluster_count);
+            metrics.reserve_exact(cluster_count as _);
+            let _hr =
+                self.GetClusterMetrics(metrics.as_mut_ptr(), cluster_count, &mut cluster_count);
+            metrics.set_len(cluster_count.min(metrics.capacity() as _) as _);
+        }
+
+        metrics
+            .into_iter()
+            .map(|metrics: dwrite::DWRITE_CLUSTER_METRICS| ClusterMetrics {
+                width: metrics.width,
+                length: metrics.length,
+                can_wrap_line_after: metrics.canWrapLineAfter() != 0,
+                is_whitespace: metrics.isWhitespace() != 0,
+                is_newline: metrics.isNewline() != 0,
+                is_soft_hyphen: metrics.isSoftHyphen() != 0,
+                is_right_to_left: metrics.isRightToLeft() != 0,
+            })
+            .collect()
+    }
+
+    pub fn hit_test_point(&self, x: f32, y: f32) -> HitTestPoint {
+        unsafe {
+            let mut is_trailing_hit = 0;
+            let mut is_inside = 0;
+            let mut metrics = mem::zeroed();
+            let _hr = self.HitTestPoint(x, y, &mut is_trailing_hit, &mut is_inside, &mut metrics);
+            HitTestPoint {
+                is_trailing_hit: is_trailing_hit != 0,
+                is_inside: is_inside != 0,
+                metrics: metrics.into(),
+            }
+        }
+    }
+
+    /// Returns the caret position for the leading or trailing edge of the text position.
+    pub fn hit_test_text_position(
+        &self,
+        text_position: u32,
+        is_trailing_hit: bool,
+    ) -> ([f32; 2], HitTestMetrics) {
+        unsafe {
+            let mut x = 0.0;
+            let mut y = 0.0;
+            let mut metrics = mem::zeroed();
+            let _hr = self.HitTestTextPosition(
+                text_position,
+                is_trailing_hit as _,
+                &mut x,
+                &mut y,
+                &mut metrics,
+            );
+            ([x, y], metrics.into())
+        }
+    }
+
+    pub fn hit_test_text_range(
+        &self,
+        text_position: u32,
+        text_length: u32,
+        origin: [f32; 2],
+    ) -> Vec<HitTestMetrics> {
+        let mut metrics = Vec::new();
+        unsafe {
+            // Query the number of ranges first.
+            let mut count = 0;
+            let _hr = self.HitTestTextRange(
+                text_position,
+                text_length,
+                origin[0],
+                origin[1],
+                ptr::null_mut(),
+                0,
+                &mut count,
+            );
+            metrics.reserve_exact(count as _);
+            let _hr = self.HitTestTextRange(
+                text_position,
+                text_length,
+                origin[0],
+                origin[1],
+                metrics.as_mut_ptr(),
+                count,
+                &mut count,
+            );
+            metrics.set_len(count.min(metrics.capacity() as _) as _);
+        }
+
+        metrics
+            .into_iter()
+            .map(|metrics: dwrite::DWRITE_HIT_TEST_METRICS| metrics.into())
+            .collect()
+    }
+
     pub fn get_overhang_metrics(&self) -> OverhangMetrics {
         unsafe {
             let mut metrics = mem::zeroed();
@@ -1,7 +1,9 @@
 use super::d3d11;
+use crate::Error;
+use std::mem;
 use std::ops::Deref;
 use std::ptr;
-use winapi::shared::{dxgi, dxgi1_2, dxgiformat::*, dxgitype};
+use winapi::shared::{dxgi, dxgi1_2, dxgi1_4, dxgiformat::*, dxgitype};
 use winapi::um::d3d11::ID3D11Texture2D;
 use winapi::Interface;
 use wio::com::ComPtr;
@@ -10,6 +12,22 @@ pub type AdapterRaw = ComPtr<dxgi::IDXGIAdapter>;
 pub type FactoryRaw = ComPtr<dxgi1_2::IDXGIFactory2>;
 pub type BackbufferRaw = ComPtr<ID3D11Texture2D>;
 
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    B8G8R8A8Unorm = DXGI_FORMAT_B8G8R8A8_UNORM,
+    R16G16B16A16Float = DXGI_FORMAT_R16G16B16A16_FLOAT,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// sRGB primaries with gamma 2.2.
+    RgbFullG22P709 = dxgitype::DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709,
+    /// Linear scRGB, used with float formats for HDR and wide gamut output.
+    RgbFullG10P709 = dxgitype::DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+}
+
 pub type SwapchainRaw = ComPtr<dxgi1_2::IDXGISwapChain1>;
 pub struct Swapchain(SwapchainRaw);
 
@@ -74,11 +92,53 @@ impl Swapchain {
         }
     }
 
-    pub fn present(&self) {
+    /// Resize the buffers, a size of zero uses the client area of the window.
+    ///
+    /// Keeps the current format if `format` is `None`.
+    /// All references to the current backbuffer must be released beforehand.
+    pub fn resize_buffers(
+        &self,
+        width: u32,
+        height: u32,
+        format: Option<Format>,
+    ) -> Result<(), Error> {
+        let format = format.map_or(DXGI_FORMAT_UNKNOWN, |format| format as _);
+        let hr = unsafe { self.ResizeBuffers(0, width, height, format, 0) };
+        Error::check(hr)
+    }
+
+    /// Whether the swapchain contents can be presented in the color space.
+    pub fn check_color_space_support(&self, color_space: ColorSpace) -> Result<bool, Error> {
+        let swapchain = self
+            .cast::<dxgi1_4::IDXGISwapChain3>()
+            .map_err(Error::Hresult)?;
+        let mut support = 0;
+        let hr = unsafe { swapchain.CheckColorSpaceSupport(color_space as _, &mut support) };
+        Error::check(hr)?;
+        Ok(support & dxgi1_4::DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT != 0)
+    }
+
+    /// Set the color space the swapchain contents are interpreted in by the compositor.
+    pub fn set_color_space(&self, color_space: ColorSpace) -> Result<(), Error> {
+        let swapchain = self
+            .cast::<dxgi1_4::IDXGISwapChain3>()
+            .map_err(Error::Hresult)?;
+        let hr = unsafe { swapchain.SetColorSpace1(color_space as _) };
+        Error::check(hr)
+    }
+
+    pub fn get_extent(&self) -> (u32, u32) {
         unsafe {
-            self.Present(1, 0);
+            let mut desc = mem::zeroed();
+            let _hr = self.GetDesc1(&mut desc);
+            (desc.Width, desc.Height)
         }
     }
+
+    pub fn present(&self) -> Result<(), Error> {
+        let hr = unsafe { self.Present(1, 0) };
+        Error::check(hr)
+    }
 }
 
 impl Deref for Swapchain {
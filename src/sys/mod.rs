@@ -1,3 +1,4 @@
+pub(crate) mod com;
 pub mod d3d11;
 pub mod direct2d;
 pub mod dwrite;
@@ -1,7 +1,259 @@
-use crate::sys;
-use piet::{Error, RoundInto};
+use crate::cache::LruCache;
+use crate::context::{Brush, Frame};
+use crate::{sys, TextBuffer};
+use kurbo::{BezPath, Point, Rect, Vec2};
+use piet::Error;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::mem;
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
+use std::rc::Rc;
 
-pub struct Text(pub(crate) sys::dwrite::Factory);
+pub struct Text {
+    factory: sys::dwrite::Factory,
+    /// Collections of the loaded fonts, one per load call in loading order.
+    collections: Vec<sys::dwrite::FontCollection>,
+    font_fallback: Option<sys::dwrite::FontFallback>,
+}
+
+impl Text {
+    pub(crate) fn new(factory: sys::dwrite::Factory) -> Self {
+        Text {
+            factory,
+            collections: Vec::new(),
+            font_fallback: None,
+        }
+    }
+
+    /// Load fonts from font data, e.g. the contents of a TTF or OTF file.
+    ///
+    /// Loaded fonts are selected by family name like installed fonts and
+    /// take precedence over installed fonts of the same family.
+    pub fn load_font(&mut self, data: &[u8]) -> Result<(), crate::Error> {
+        self.load_fonts(&[data])
+    }
+
+    /// Load fonts from the data of multiple font files.
+    ///
+    /// Families present in fonts loaded earlier are replaced by the new fonts.
+    pub fn load_fonts(&mut self, fonts: &[&[u8]]) -> Result<(), crate::Error> {
+        let files = fonts
+            .iter()
+            .map(|data| self.factory.create_font_file_from_memory(data))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.add_font_files(&files)
+    }
+
+    /// Load fonts from a font file on disk.
+    pub fn load_font_file(&mut self, path: impl AsRef<Path>) -> Result<(), crate::Error> {
+        self.load_font_files(&[path])
+    }
+
+    /// Load fonts from multiple font files on disk.
+    pub fn load_font_files(&mut self, paths: &[impl AsRef<Path>]) -> Result<(), crate::Error> {
+        let files = paths
+            .iter()
+            .map(|path| self.factory.create_font_file_reference(path.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.add_font_files(&files)
+    }
+
+    fn add_font_files(&mut self, files: &[sys::dwrite::FontFile]) -> Result<(), crate::Error> {
+        let collection = self.factory.create_font_collection(files)?;
+        self.collections.push(collection);
+        Ok(())
+    }
+
+    /// Font families of the loaded fonts followed by the installed fonts.
+    ///
+    /// Names are localized to the locale of the current user.
+    pub fn font_families(&self) -> Result<Vec<FontFamilyInfo>, crate::Error> {
+        let locale = sys::dwrite::user_default_locale();
+        let mut families = Vec::new();
+        for collection in self.collections.iter().rev() {
+            collect_font_families(collection, &locale, true, &mut families)?;
+        }
+        // Families loaded again are listed once, with the most recent fonts.
+        let mut names = HashSet::new();
+        families.retain(|family| names.insert(family.name.clone()));
+        let system = self.factory.get_system_font_collection()?;
+        collect_font_families(&system, &locale, false, &mut families)?;
+        Ok(families)
+    }
+
+    /// Create rendering parameters for `Frame::set_text_rendering_params`.
+    ///
+    /// `cleartype_level` ranges from 0 for grayscale to 1 for full ClearType.
+    pub fn create_rendering_params(
+        &self,
+        gamma: f32,
+        enhanced_contrast: f32,
+        cleartype_level: f32,
+        pixel_geometry: sys::dwrite::PixelGeometry,
+        rendering_mode: sys::dwrite::RenderingMode,
+    ) -> Result<sys::dwrite::RenderingParams, crate::Error> {
+        self.factory.create_custom_rendering_params(
+            gamma,
+            enhanced_contrast,
+            cleartype_level,
+            pixel_geometry,
+            rendering_mode,
+        )
+    }
+
+    /// Start building a font fallback, see `FontFallbackBuilder`.
+    pub fn font_fallback_builder(&self) -> Result<FontFallbackBuilder, crate::Error> {
+        Ok(FontFallbackBuilder {
+            builder: self.factory.create_font_fallback_builder()?,
+            factory: self.factory.clone(),
+            collections: self.collections.clone(),
+        })
+    }
+
+    /// Font fallback of text layouts created afterwards, `None` for the system fallback.
+    pub fn set_font_fallback(&mut self, fallback: Option<sys::dwrite::FontFallback>) {
+        self.font_fallback = fallback;
+    }
+
+    /// Whether the font family is available in the loaded or installed fonts.
+    pub fn has_font_family(&self, family: &str) -> bool {
+        self.loaded_collection(family).is_some() || self.is_installed(family)
+    }
+
+    fn loaded_collection(&self, family: &str) -> Option<&sys::dwrite::FontCollection> {
+        find_loaded_collection(&self.collections, family)
+    }
+
+    fn is_installed(&self, family: &str) -> bool {
+        self.factory
+            .get_system_font_collection()
+            .map(|system| system.find_family_name(family).is_some())
+            .unwrap_or(false)
+    }
+}
+
+/// Builds font fallbacks mapping Unicode ranges to font families.
+///
+/// Mappings added first are tried first, add the system fallback last to
+/// cover the characters missing in all mapped families.
+pub struct FontFallbackBuilder {
+    builder: sys::dwrite::FontFallbackBuilder,
+    factory: sys::dwrite::Factory,
+    /// Collections of the fonts loaded when the builder was created.
+    collections: Vec<sys::dwrite::FontCollection>,
+}
+
+impl FontFallbackBuilder {
+    /// Try the families in order for characters in the ranges.
+    ///
+    /// Loaded fonts take precedence over installed fonts of the same family.
+    pub fn add_mapping(
+        self,
+        ranges: &[RangeInclusive<char>],
+        families: &[&str],
+    ) -> Result<Self, crate::Error> {
+        let ranges = ranges
+            .iter()
+            .map(|range| *range.start() as u32..=*range.end() as u32)
+            .collect::<Vec<_>>();
+        // Address of the collection containing the family, zero for installed fonts.
+        let collection_of = |family: &str| {
+            find_loaded_collection(&self.collections, family)
+                .map_or(0, |collection| collection.as_raw() as usize)
+        };
+
+        // Each mapping refers to a single collection, split the families into
+        // consecutive groups of the same collection to keep their order.
+        let mut start = 0;
+        while start < families.len() {
+            let collection = find_loaded_collection(&self.collections, families[start]);
+            let key = collection_of(families[start]);
+            let len = families[start..]
+                .iter()
+                .take_while(|family| collection_of(family) == key)
+                .count();
+            self.builder.add_mapping(
+                &ranges,
+                &families[start..start + len],
+                collection,
+                None,
+                None,
+                1.0,
+            )?;
+            start += len;
+        }
+        Ok(self)
+    }
+
+    /// Append the fallback of the system, usually added last.
+    pub fn add_system_fallback(self) -> Result<Self, crate::Error> {
+        let system = self.factory.get_system_font_fallback()?;
+        self.builder.add_mappings(&system)?;
+        Ok(self)
+    }
+
+    pub fn build(self) -> Result<sys::dwrite::FontFallback, crate::Error> {
+        self.builder.create_font_fallback()
+    }
+}
+
+/// Most recently loaded collection containing the family.
+fn find_loaded_collection<'a>(
+    collections: &'a [sys::dwrite::FontCollection],
+    family: &str,
+) -> Option<&'a sys::dwrite::FontCollection> {
+    collections
+        .iter()
+        .rev()
+        .find(|collection| collection.find_family_name(family).is_some())
+}
+
+fn collect_font_families(
+    collection: &sys::dwrite::FontCollection,
+    locale: &str,
+    loaded: bool,
+    families: &mut Vec<FontFamilyInfo>,
+) -> Result<(), crate::Error> {
+    for i in 0..collection.get_font_family_count() {
+        let family = collection.get_font_family(i)?;
+        let name = family.get_family_names()?.get_localized(locale)?;
+        let mut faces = Vec::new();
+        for j in 0..family.get_font_count() {
+            let font = family.get_font(j)?;
+            faces.push(FontFaceInfo {
+                name: font.get_face_names()?.get_localized(locale)?,
+                weight: font.get_weight(),
+                style: font.get_style(),
+                stretch: font.get_stretch(),
+            });
+        }
+        families.push(FontFamilyInfo {
+            name,
+            loaded,
+            faces,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct FontFamilyInfo {
+    /// Family name, used to select the family in `new_font_by_name`.
+    pub name: String,
+    /// Whether the family comes from fonts loaded with `Text::load_font`.
+    pub loaded: bool,
+    pub faces: Vec<FontFaceInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FontFaceInfo {
+    /// Face name inside the family, e.g. `Bold Italic`.
+    pub name: String,
+    pub weight: sys::dwrite::FontWeight,
+    pub style: sys::dwrite::FontStyle,
+    pub stretch: sys::dwrite::FontStretch,
+}
 
 impl piet::Text for Text {
     type Font = Font;
@@ -10,17 +262,21 @@ impl piet::Text for Text {
 
     type TextLayoutBuilder = TextLayoutBuilder;
 
-    fn new_font_by_name(
-        &mut self,
-        name: &str,
-        size: f64,
-    ) -> Result<Self::FontBuilder, Error> {
+    /// Fails with `FontFamilyNotFound` if the family is neither loaded nor installed.
+    fn new_font_by_name(&mut self, name: &str, size: f64) -> Result<Self::FontBuilder, Error> {
+        let collection = self.loaded_collection(name).cloned();
+        if collection.is_none() && !self.is_installed(name) {
+            return Err(crate::Error::FontFamilyNotFound.into());
+        }
         Ok(FontBuilder {
-            text_format: self.0.create_text_format(
-                name,
-                size.round_into(),
-                sys::dwrite::FontWeight::Normal,
-            ),
+            factory: self.factory.clone(),
+            collection,
+            family: name.to_owned(),
+            size: size as _,
+            weight: sys::dwrite::FontWeight::Normal,
+            style: sys::dwrite::FontStyle::Normal,
+            stretch: sys::dwrite::FontStretch::Normal,
+            locale: None,
         })
     }
 
@@ -30,15 +286,609 @@ impl piet::Text for Text {
         text: &str,
     ) -> Result<Self::TextLayoutBuilder, Error> {
         Ok(TextLayoutBuilder {
-            text_layout: self.0.create_text_layout(text, &font.0, 1e6, 1e6), // hmm no widht/height?
+            factory: self.factory.clone(),
+            format: font.0.clone(),
+            buffer: TextBuffer::new(text),
+            max_width: None,
+            max_height: None,
+            word_wrapping: sys::dwrite::WordWrapping::Wrap,
+            text_alignment: sys::dwrite::TextAlignment::Leading,
+            paragraph_alignment: sys::dwrite::ParagraphAlignment::Near,
+            reading_direction: None,
+            flow_direction: sys::dwrite::FlowDirection::TopToBottom,
+            line_spacing: LineSpacing::Default,
+            measuring_mode: sys::dwrite::MeasuringMode::Natural,
+            pixels_per_dip: 1.0,
+            trimming: None,
+            font_fallback: self.font_fallback.clone(),
+            attributes: Vec::new(),
         })
     }
 }
 
-pub struct TextLayout(pub(crate) sys::dwrite::TextLayout);
+/// Extent of an axis without constraint while measuring the text.
+const UNCONSTRAINED: f32 = 1e6;
+
+#[derive(Clone, Debug)]
+pub struct LineMetrics {
+    /// Byte range of the line, including trailing whitespace and newline.
+    pub range: Range<usize>,
+    /// Length of the trailing whitespace in bytes, including the newline.
+    pub trailing_whitespace: usize,
+    /// Length of the newline in bytes.
+    pub newline_length: usize,
+    pub height: f64,
+    /// Distance from the top of the line to its baseline.
+    pub baseline: f64,
+    pub is_trimmed: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClusterMetrics {
+    /// Byte range of the cluster.
+    pub range: Range<usize>,
+    pub width: f64,
+    pub can_wrap_line_after: bool,
+    pub is_whitespace: bool,
+    pub is_newline: bool,
+    pub is_soft_hyphen: bool,
+    pub is_right_to_left: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct HitTestPoint {
+    /// Byte offset of the character cluster hit.
+    pub index: usize,
+    /// Length of the character cluster in bytes.
+    pub length: usize,
+    /// Whether the point is on the trailing side of the cluster.
+    pub is_trailing_hit: bool,
+    /// Whether the point lies inside the text bounds.
+    pub is_inside: bool,
+    /// Whether the cluster is part of a right-to-left run.
+    pub is_right_to_left: bool,
+}
+
+impl HitTestPoint {
+    /// Byte offset of the caret position closest to the point.
+    pub fn caret_index(&self) -> usize {
+        if self.is_trailing_hit {
+            self.index + self.length
+        } else {
+            self.index
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct HitTestPosition {
+    /// Top of the caret.
+    pub point: Point,
+    /// Height of the caret.
+    pub height: f64,
+    /// Whether the caret is placed at a right-to-left run.
+    pub is_right_to_left: bool,
+}
+
+pub struct TextLayout {
+    pub(crate) layout: sys::dwrite::TextLayout,
+    buffer: TextBuffer,
+    /// Wrapping of the builder, lines don't wrap while the line axis has no constraint.
+    word_wrapping: sys::dwrite::WordWrapping,
+    /// Whether lines run along the height of the layout box.
+    is_vertical: bool,
+    /// Whether the width and height have no constraint, these axes fit the text.
+    is_unconstrained: [bool; 2],
+    /// Brushes of the foreground attributes, passed to custom renderers.
+    foregrounds: Vec<Brush>,
+    inline_objects: Vec<InlineBox>,
+    inline_queue: InlineQueue,
+}
+
+impl TextLayout {
+    /// Text of the layout.
+    pub fn buffer(&self) -> &TextBuffer {
+        &self.buffer
+    }
+
+    /// Find the text position under the point, relative to the layout origin.
+    pub fn hit_test_point(&self, point: Point) -> HitTestPoint {
+        let hit = self.layout.hit_test_point(point.x as _, point.y as _);
+        let start = hit.metrics.text_position;
+        let range = self
+            .buffer
+            .utf16_range_to_utf8(start..start + hit.metrics.length);
+        HitTestPoint {
+            index: range.start,
+            length: range.end - range.start,
+            is_trailing_hit: hit.is_trailing_hit,
+            is_inside: hit.is_inside,
+            is_right_to_left: hit.metrics.bidi_level % 2 == 1,
+        }
+    }
+
+    /// Caret position in front of the byte offset `index`.
+    ///
+    /// In right-to-left runs the caret is on the right side of the character.
+    pub fn hit_test_text_position(&self, index: usize) -> HitTestPosition {
+        self.hit_test_caret(index, false)
+    }
+
+    /// Caret position behind the character at the byte offset `index`.
+    ///
+    /// Differs from the position in front of the next character at the boundary of
+    /// left-to-right and right-to-left runs, use it for the caret of a trailing
+    /// `HitTestPoint` with the index of the hit character.
+    pub fn hit_test_text_position_trailing(&self, index: usize) -> HitTestPosition {
+        self.hit_test_caret(index, true)
+    }
+
+    fn hit_test_caret(&self, index: usize, is_trailing_hit: bool) -> HitTestPosition {
+        let position = self.buffer.utf8_to_utf16(index);
+        let (point, metrics) = self.layout.hit_test_text_position(position, is_trailing_hit);
+        HitTestPosition {
+            point: Point::new(point[0] as _, point[1] as _),
+            height: metrics.height as _,
+            is_right_to_left: metrics.bidi_level % 2 == 1,
+        }
+    }
+
+    /// Selection rectangles covering the byte range.
+    pub fn hit_test_range(&self, range: Range<usize>) -> Vec<Rect> {
+        let range = self.buffer.utf8_range_to_utf16(range);
+        self.layout
+            .hit_test_text_range(range.start, range.end - range.start, [0.0, 0.0])
+            .into_iter()
+            .map(|metrics| {
+                Rect::new(
+                    metrics.left as _,
+                    metrics.top as _,
+                    (metrics.left + metrics.width) as _,
+                    (metrics.top + metrics.height) as _,
+                )
+            })
+            .collect()
+    }
+
+    /// Height of the formatted text, including all lines.
+    pub fn height(&self) -> f64 {
+        self.layout.get_metrics().height as _
+    }
+
+    /// Width of the formatted text, including trailing whitespace.
+    pub fn width_including_trailing_whitespace(&self) -> f64 {
+        self.layout.get_metrics().width_incl_trailing_whitespaces as _
+    }
+
+    /// Draw the layout with a custom renderer, `origin` is the top-left corner of the layout box.
+    ///
+    /// Text positions passed to the renderer are UTF-16 offsets, see `buffer`
+    /// for converting them to byte offsets. Inline objects of the builder are
+    /// passed after the text.
+    pub fn draw(&self, origin: Point, renderer: &mut dyn TextRenderer) -> Result<(), crate::Error> {
+        self.clear_inline_objects();
+        let mut layout_renderer = LayoutRenderer {
+            renderer: &mut *renderer,
+            foregrounds: &self.foregrounds,
+            result: Ok(()),
+        };
+        self.layout.draw(&mut layout_renderer, [origin.x as _, origin.y as _])?;
+        layout_renderer.result?;
+        for (index, baseline_origin) in self.take_inline_objects() {
+            renderer.draw_inline_object(baseline_origin, index);
+        }
+        Ok(())
+    }
+
+    /// Outlines of the glyphs, relative to the top-left corner of the layout box.
+    ///
+    /// Underlines, strikethroughs and inline objects added with `TextLayoutBuilder::inline_object`
+    /// are not included.
+    pub fn outline(&self) -> Result<BezPath, crate::Error> {
+        let mut collector = OutlineCollector {
+            path: BezPath::new(),
+            result: Ok(()),
+        };
+        self.draw(Point::new(0.0, 0.0), &mut collector)?;
+        collector.result.map(|()| collector.path)
+    }
+
+    /// Forget the inline objects recorded by previous draws, e.g. draws which failed
+    /// before the objects were passed on.
+    pub(crate) fn clear_inline_objects(&self) {
+        self.inline_queue.borrow_mut().clear();
+    }
+
+    /// Index and baseline origin of the inline objects drawn since the queue was cleared.
+    fn take_inline_objects(&self) -> Vec<(usize, Point)> {
+        let queue = mem::take(&mut *self.inline_queue.borrow_mut());
+        queue
+            .into_iter()
+            .map(|(index, origin)| {
+                let baseline = self.inline_objects[index].metrics.baseline;
+                (index, Point::new(origin[0] as _, (origin[1] + baseline) as _))
+            })
+            .collect()
+    }
+
+    /// Call the draw callbacks of the inline objects drawn since the queue was cleared.
+    pub(crate) fn draw_inline_objects(&self, frame: &mut Frame) {
+        for (index, baseline_origin) in self.take_inline_objects() {
+            (self.inline_objects[index].draw)(frame, baseline_origin);
+        }
+    }
+
+    /// Distance from the top of the layout box to the baseline of the first line.
+    pub fn first_baseline(&self) -> f64 {
+        let top = self.layout.get_metrics().top;
+        let baseline = self
+            .layout
+            .get_line_metrics()
+            .first()
+            .map_or(0.0, |line| line.baseline);
+        (top + baseline) as _
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.layout.get_metrics().line_count as _
+    }
+
+    pub fn metrics(&self) -> sys::dwrite::TextMetrics {
+        self.layout.get_metrics()
+    }
+
+    pub fn line_metrics(&self) -> Vec<LineMetrics> {
+        let mut start = 0;
+        self.layout
+            .get_line_metrics()
+            .into_iter()
+            .map(|metrics| {
+                let end = start + metrics.length;
+                let range = self.buffer.utf16_range_to_utf8(start..end);
+                let newline = self.buffer.utf16_to_utf8(end - metrics.newline_length);
+                let whitespace = self
+                    .buffer
+                    .utf16_to_utf8(end - metrics.trailing_whitespace_length);
+                start = end;
+
+                LineMetrics {
+                    trailing_whitespace: range.end - whitespace,
+                    newline_length: range.end - newline,
+                    range,
+                    height: metrics.height as _,
+                    baseline: metrics.baseline as _,
+                    is_trimmed: metrics.is_trimmed,
+                }
+            })
+            .collect()
+    }
+
+    pub fn cluster_metrics(&self) -> Vec<ClusterMetrics> {
+        let mut start = 0;
+        self.layout
+            .get_cluster_metrics()
+            .into_iter()
+            .map(|metrics| {
+                let end = start + metrics.length as u32;
+                let range = self.buffer.utf16_range_to_utf8(start..end);
+                start = end;
+
+                ClusterMetrics {
+                    range,
+                    width: metrics.width as _,
+                    can_wrap_line_after: metrics.can_wrap_line_after,
+                    is_whitespace: metrics.is_whitespace,
+                    is_newline: metrics.is_newline,
+                    is_soft_hyphen: metrics.is_soft_hyphen,
+                    is_right_to_left: metrics.is_right_to_left,
+                }
+            })
+            .collect()
+    }
+
+    /// Change the maximum width and reflow the text without recreating the layout.
+    pub fn set_max_width(&mut self, width: f64) -> Result<(), Error> {
+        self.is_unconstrained[0] = false;
+        self.layout.set_max_width(width as _)?;
+        self.fit_unconstrained()?;
+        Ok(())
+    }
+
+    /// Change the maximum height without recreating the layout.
+    pub fn set_max_height(&mut self, height: f64) -> Result<(), Error> {
+        self.is_unconstrained[1] = false;
+        self.layout.set_max_height(height as _)?;
+        self.fit_unconstrained()?;
+        Ok(())
+    }
+
+    /// Shrink the axes without constraint to the extent of the formatted text, the
+    /// alignment along these axes is relative to the text instead of a huge box.
+    fn fit_unconstrained(&self) -> Result<(), crate::Error> {
+        let [width, height] = self.is_unconstrained;
+        if width {
+            self.layout.set_max_width(UNCONSTRAINED)?;
+        }
+        if height {
+            self.layout.set_max_height(UNCONSTRAINED)?;
+        }
+        // Lines can't wrap at the end of the text, as the end moves with the text.
+        let is_line_axis_unconstrained = if self.is_vertical { height } else { width };
+        self.layout.set_word_wrapping(if is_line_axis_unconstrained {
+            sys::dwrite::WordWrapping::NoWrap
+        } else {
+            self.word_wrapping
+        })?;
+        let metrics = self.layout.get_metrics();
+        if width {
+            self.layout.set_max_width(metrics.width)?;
+        }
+        if height {
+            self.layout.set_max_height(metrics.height)?;
+        }
+        Ok(())
+    }
+
+    /// Distances the glyphs extend beyond the layout box.
+    pub fn overhang_metrics(&self) -> sys::dwrite::OverhangMetrics {
+        self.layout.get_overhang_metrics()
+    }
+}
+
+/// Whether the UTF-16 code unit ends a paragraph in the bidi algorithm.
+fn is_paragraph_separator(c: u16) -> bool {
+    matches!(c, 0x0A | 0x0D | 0x1C..=0x1E | 0x85 | 0x2029)
+}
+
+/// Base direction of the text, from the first strong character of the first paragraph
+/// following rules P2 and P3 of UAX #9, left-to-right without strong characters.
+///
+/// The levels are resolved by DirectWrite. Characters inside isolates are skipped as
+/// required by P2, unlike P2 characters inside explicit embeddings are skipped as well.
+fn detect_reading_direction(
+    factory: &sys::dwrite::Factory,
+    text: &[u16],
+    locale: &str,
+) -> Result<sys::dwrite::ReadingDirection, crate::Error> {
+    use sys::dwrite::ReadingDirection::{LeftToRight, RightToLeft};
+
+    let end = text
+        .iter()
+        .position(|&c| is_paragraph_separator(c))
+        .unwrap_or(text.len());
+    let paragraph = &text[..end];
+    if paragraph.is_empty() {
+        return Ok(LeftToRight);
+    }
+    let analyzer = factory.create_text_analyzer()?;
+    // Levels of the paragraph following a strong character, which keeps the
+    // characters in front of the first strong character of the paragraph from
+    // resolving to the base direction.
+    let behind = |prefix: char, direction| -> Result<Vec<u8>, crate::Error> {
+        let mut prefixed = Vec::with_capacity(paragraph.len() + 1);
+        prefixed.push(prefix as u16);
+        prefixed.extend_from_slice(paragraph);
+        let mut levels = analyzer.analyze_bidi(&prefixed, locale, direction)?;
+        levels.remove(0);
+        Ok(levels)
+    };
+    // Only strong right-to-left characters are raised to level 1 behind a
+    // left-to-right character in a left-to-right paragraph.
+    let behind_left = behind('a', LeftToRight)?;
+    // Strong left-to-right characters stay at level 0 behind a right-to-left
+    // character and are raised to level 2 in a right-to-left paragraph, numbers
+    // are raised in both cases.
+    let behind_right = behind('\u{05D0}', LeftToRight)?;
+    let right_to_left = analyzer.analyze_bidi(paragraph, locale, RightToLeft)?;
+    for i in 0..paragraph.len() {
+        if behind_left[i] == 1 {
+            return Ok(RightToLeft);
+        }
+        if behind_right[i] == 0 && right_to_left[i] == 2 {
+            return Ok(LeftToRight);
+        }
+    }
+    Ok(LeftToRight)
+}
+
+/// Receives the glyph runs, lines and inline objects of a text layout, see `TextLayout::draw`.
+///
+/// Positions are in DIPs. `foreground` is the brush set with `TextLayoutBuilder::foreground`
+/// for the range, `None` for the default brush of the renderer.
+pub trait TextRenderer {
+    fn draw_glyph_run(
+        &mut self,
+        baseline_origin: Point,
+        measuring_mode: sys::dwrite::MeasuringMode,
+        glyph_run: &sys::dwrite::GlyphRun,
+        description: &sys::dwrite::GlyphRunDescription,
+        foreground: Option<&Brush>,
+    );
+
+    fn draw_underline(
+        &mut self,
+        _baseline_origin: Point,
+        _underline: &sys::dwrite::Decoration,
+        _foreground: Option<&Brush>,
+    ) {
+    }
+
+    fn draw_strikethrough(
+        &mut self,
+        _baseline_origin: Point,
+        _strikethrough: &sys::dwrite::Decoration,
+        _foreground: Option<&Brush>,
+    ) {
+    }
+
+    /// Inline object added with `TextLayoutBuilder::inline_object`, `index` counts the
+    /// inline objects in the order they were added.
+    fn draw_inline_object(&mut self, _baseline_origin: Point, _index: usize) {}
+
+    fn is_pixel_snapping_disabled(&self) -> bool {
+        false
+    }
+
+    /// Transform from DIPs to pixels as `[m11, m12, m21, m22, dx, dy]`, used for pixel snapping.
+    fn current_transform(&self) -> [f32; 6] {
+        [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+    }
+
+    fn pixels_per_dip(&self) -> f32 {
+        1.0
+    }
+}
+
+/// Forwards the DirectWrite layout to the renderer of a text layout, drawing effects
+/// are mapped back to the foreground brushes.
+struct LayoutRenderer<'a> {
+    renderer: &'a mut dyn TextRenderer,
+    foregrounds: &'a [Brush],
+    /// First error of drawing inline objects.
+    result: Result<(), crate::Error>,
+}
+
+impl<'a> LayoutRenderer<'a> {
+    fn foreground(&self, effect: Option<sys::dwrite::DrawingEffect>) -> Option<&'a Brush> {
+        let effect = effect?;
+        self.foregrounds.iter().find(|brush| effect.is_brush(*brush))
+    }
+}
+
+fn to_point(p: [f32; 2]) -> Point {
+    Point::new(p[0] as _, p[1] as _)
+}
+
+impl sys::dwrite::TextRenderer for LayoutRenderer<'_> {
+    fn draw_glyph_run(
+        &mut self,
+        baseline_origin: [f32; 2],
+        measuring_mode: sys::dwrite::MeasuringMode,
+        glyph_run: &sys::dwrite::GlyphRun,
+        description: &sys::dwrite::GlyphRunDescription,
+        effect: Option<sys::dwrite::DrawingEffect>,
+    ) {
+        let foreground = self.foreground(effect);
+        self.renderer.draw_glyph_run(
+            to_point(baseline_origin),
+            measuring_mode,
+            glyph_run,
+            description,
+            foreground,
+        );
+    }
+
+    fn draw_underline(
+        &mut self,
+        baseline_origin: [f32; 2],
+        underline: &sys::dwrite::Decoration,
+        effect: Option<sys::dwrite::DrawingEffect>,
+    ) {
+        let foreground = self.foreground(effect);
+        self.renderer.draw_underline(to_point(baseline_origin), underline, foreground);
+    }
+
+    fn draw_strikethrough(
+        &mut self,
+        baseline_origin: [f32; 2],
+        strikethrough: &sys::dwrite::Decoration,
+        effect: Option<sys::dwrite::DrawingEffect>,
+    ) {
+        let foreground = self.foreground(effect);
+        self.renderer.draw_strikethrough(to_point(baseline_origin), strikethrough, foreground);
+    }
+
+    // Trimming signs draw their glyphs through this renderer, inline objects of
+    // the builder record their position for `TextLayout::draw`.
+    fn draw_inline_object(
+        &mut self,
+        origin: [f32; 2],
+        object: &sys::dwrite::InlineObject,
+        is_sideways: bool,
+        is_right_to_left: bool,
+        effect: Option<sys::dwrite::DrawingEffect>,
+    ) {
+        let result = object.draw(self, origin, is_sideways, is_right_to_left, effect);
+        if self.result.is_ok() {
+            self.result = result;
+        }
+    }
+
+    fn is_pixel_snapping_disabled(&self) -> bool {
+        self.renderer.is_pixel_snapping_disabled()
+    }
+
+    fn current_transform(&self) -> [f32; 6] {
+        self.renderer.current_transform()
+    }
+
+    fn pixels_per_dip(&self) -> f32 {
+        self.renderer.pixels_per_dip()
+    }
+}
+
+/// Outlines of the glyphs of a run placed at the baseline origin.
+pub fn glyph_run_outline(
+    glyph_run: &sys::dwrite::GlyphRun,
+    baseline_origin: Point,
+) -> Result<BezPath, crate::Error> {
+    let mut path = BezPath::new();
+    append_outline(
+        &mut path,
+        &glyph_run.get_outline()?,
+        baseline_origin.to_vec2(),
+    );
+    Ok(path)
+}
+
+fn append_outline(path: &mut BezPath, outline: &[sys::dwrite::OutlineElement], offset: Vec2) {
+    use sys::dwrite::OutlineElement;
+    let point = |p: [f32; 2]| Point::new(p[0] as _, p[1] as _) + offset;
+    for element in outline {
+        match *element {
+            OutlineElement::BeginFigure(p) => path.move_to(point(p)),
+            OutlineElement::Line(p) => path.line_to(point(p)),
+            OutlineElement::Bezier(p0, p1, p2) => path.curve_to(point(p0), point(p1), point(p2)),
+            OutlineElement::EndFigure { closed: true } => path.close_path(),
+            OutlineElement::EndFigure { closed: false } => (),
+        }
+    }
+}
+
+/// Text renderer appending the outlines of all glyph runs to a path.
+struct OutlineCollector {
+    path: BezPath,
+    /// First error, the remaining runs are skipped.
+    result: Result<(), crate::Error>,
+}
+
+impl TextRenderer for OutlineCollector {
+    fn draw_glyph_run(
+        &mut self,
+        baseline_origin: Point,
+        _measuring_mode: sys::dwrite::MeasuringMode,
+        glyph_run: &sys::dwrite::GlyphRun,
+        _description: &sys::dwrite::GlyphRunDescription,
+        _foreground: Option<&Brush>,
+    ) {
+        if self.result.is_err() {
+            return;
+        }
+        match glyph_run.get_outline() {
+            Ok(outline) => append_outline(&mut self.path, &outline, baseline_origin.to_vec2()),
+            Err(err) => self.result = Err(err),
+        }
+    }
+
+    // Outlines are independent of the device, glyph positions must not be rounded.
+    fn is_pixel_snapping_disabled(&self) -> bool {
+        true
+    }
+}
+
 impl piet::TextLayout for TextLayout {
     fn width(&self) -> f64 {
-        unimplemented!()
+        self.metrics().width as _
     }
 }
 
@@ -46,26 +896,920 @@ pub struct Font(sys::dwrite::TextFormat);
 
 impl piet::Font for Font {}
 
+/// Design metrics of a font, scaled to the font size.
+#[derive(Copy, Clone, Debug)]
+pub struct FontMetrics {
+    pub size: f64,
+    /// Distance from the baseline to the top of the line.
+    pub ascent: f64,
+    /// Distance from the baseline to the bottom of the line.
+    pub descent: f64,
+    pub line_gap: f64,
+    pub cap_height: f64,
+    pub x_height: f64,
+    /// Offset of the underline from the baseline, positive values are above the baseline.
+    pub underline_position: f64,
+    pub underline_thickness: f64,
+    /// Offset of the strikethrough from the baseline, positive values are above the baseline.
+    pub strikethrough_position: f64,
+    pub strikethrough_thickness: f64,
+}
+
+impl Font {
+    pub fn metrics(&self) -> Result<FontMetrics, crate::Error> {
+        let metrics = self.font_face()?.get_metrics();
+        let size = self.size();
+        let scale = size / metrics.design_units_per_em as f64;
+        Ok(FontMetrics {
+            size,
+            ascent: metrics.ascent as f64 * scale,
+            descent: metrics.descent as f64 * scale,
+            line_gap: metrics.line_gap as f64 * scale,
+            cap_height: metrics.cap_height as f64 * scale,
+            x_height: metrics.x_height as f64 * scale,
+            underline_position: metrics.underline_position as f64 * scale,
+            underline_thickness: metrics.underline_thickness as f64 * scale,
+            strikethrough_position: metrics.strikethrough_position as f64 * scale,
+            strikethrough_thickness: metrics.strikethrough_thickness as f64 * scale,
+        })
+    }
+
+    pub fn size(&self) -> f64 {
+        self.0.get_font_size() as _
+    }
+
+    /// Font face the family and properties of the font resolve to.
+    ///
+    /// Used for looking up glyphs and building glyph runs drawn with `Frame::draw_glyph_run`.
+    pub fn font_face(&self) -> Result<sys::dwrite::FontFace, crate::Error> {
+        let collection = self
+            .0
+            .get_font_collection()
+            .ok_or(crate::Error::FontFamilyNotFound)?;
+        let index = collection
+            .find_family_name(&self.0.get_font_family_name())
+            .ok_or(crate::Error::FontFamilyNotFound)?;
+        let font = collection.get_font_family(index)?.get_first_matching_font(
+            self.0.get_font_weight(),
+            self.0.get_font_stretch(),
+            self.0.get_font_style(),
+        )?;
+        font.create_font_face()
+    }
+}
+
 pub struct FontBuilder {
-    text_format: sys::dwrite::TextFormat,
+    factory: sys::dwrite::Factory,
+    /// Collection of loaded fonts if it contains the family.
+    collection: Option<sys::dwrite::FontCollection>,
+    family: String,
+    size: f32,
+    weight: sys::dwrite::FontWeight,
+    style: sys::dwrite::FontStyle,
+    stretch: sys::dwrite::FontStretch,
+    locale: Option<String>,
+}
+
+impl FontBuilder {
+    pub fn weight(mut self, weight: sys::dwrite::FontWeight) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Select the italic or oblique style of the font.
+    pub fn style(mut self, style: sys::dwrite::FontStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn stretch(mut self, stretch: sys::dwrite::FontStretch) -> Self {
+        self.stretch = stretch;
+        self
+    }
+
+    /// Locale used for shaping and line breaking, e.g. `en-US`.
+    ///
+    /// Defaults to the locale of the current user.
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_owned());
+        self
+    }
 }
 
 impl piet::FontBuilder for FontBuilder {
     type Out = Font;
 
     fn build(self) -> Result<Self::Out, Error> {
-        Ok(Font(self.text_format))
+        let locale = self.locale.unwrap_or_else(sys::dwrite::user_default_locale);
+        let text_format = self.factory.create_text_format(
+            &self.family,
+            self.collection.as_ref(),
+            self.size,
+            self.weight,
+            self.style,
+            self.stretch,
+            &locale,
+        )?;
+        Ok(Font(text_format))
     }
 }
 
+#[derive(Clone)]
+enum Attribute {
+    FontFamily(String),
+    FontSize(f32),
+    FontWeight(sys::dwrite::FontWeight),
+    FontStyle(sys::dwrite::FontStyle),
+    FontStretch(sys::dwrite::FontStretch),
+    Underline(bool),
+    Strikethrough(bool),
+    Foreground(Brush),
+    FontFeatures(Vec<(sys::dwrite::FontFeatureTag, u32)>),
+    InlineObject(InlineBox),
+}
+
+impl Attribute {
+    /// Comparable representation, `None` for brushes and inline objects.
+    fn cache_key(&self) -> Option<AttributeKey> {
+        Some(match *self {
+            Attribute::FontFamily(ref family) => AttributeKey::FontFamily(family.clone()),
+            Attribute::FontSize(size) => AttributeKey::FontSize(size.to_bits()),
+            Attribute::FontWeight(weight) => AttributeKey::FontWeight(weight),
+            Attribute::FontStyle(style) => AttributeKey::FontStyle(style),
+            Attribute::FontStretch(stretch) => AttributeKey::FontStretch(stretch),
+            Attribute::Underline(underline) => AttributeKey::Underline(underline),
+            Attribute::Strikethrough(strikethrough) => AttributeKey::Strikethrough(strikethrough),
+            Attribute::FontFeatures(ref features) => AttributeKey::FontFeatures(features.clone()),
+            Attribute::Foreground(_) | Attribute::InlineObject(_) => return None,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum AttributeKey {
+    FontFamily(String),
+    FontSize(u32),
+    FontWeight(sys::dwrite::FontWeight),
+    FontStyle(sys::dwrite::FontStyle),
+    FontStretch(sys::dwrite::FontStretch),
+    Underline(bool),
+    Strikethrough(bool),
+    FontFeatures(Vec<(sys::dwrite::FontFeatureTag, u32)>),
+}
+
+/// Everything a layout built by a `TextLayoutBuilder` depends on, floats are compared bitwise.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct LayoutKey {
+    text: String,
+    /// Address of the font collection, cached layouts keep the collection
+    /// alive so the address is not reused while the key is stored.
+    collection: usize,
+    family: String,
+    locale: String,
+    size: u32,
+    weight: sys::dwrite::FontWeight,
+    style: sys::dwrite::FontStyle,
+    stretch: sys::dwrite::FontStretch,
+    max_width: Option<u64>,
+    max_height: Option<u64>,
+    word_wrapping: sys::dwrite::WordWrapping,
+    text_alignment: sys::dwrite::TextAlignment,
+    paragraph_alignment: sys::dwrite::ParagraphAlignment,
+    reading_direction: Option<sys::dwrite::ReadingDirection>,
+    flow_direction: sys::dwrite::FlowDirection,
+    line_spacing: (u8, u64, u64),
+    measuring_mode: sys::dwrite::MeasuringMode,
+    pixels_per_dip: u64,
+    trimming: Option<Trimming>,
+    /// Address of the font fallback, kept alive by the cached layout like the collection.
+    font_fallback: usize,
+    attributes: Vec<(Range<usize>, AttributeKey)>,
+}
+
+/// Least recently used cache of text layouts, for text measured or drawn repeatedly.
+///
+/// Layouts with foreground brushes or inline objects are built without
+/// being cached, as these attributes can not be compared.
+pub struct TextLayoutCache {
+    layouts: LruCache<LayoutKey, Rc<TextLayout>>,
+}
+
+impl TextLayoutCache {
+    /// Cache holding at most `max_layouts` layouts of `max_text_len` bytes of text in total.
+    pub fn new(max_layouts: usize, max_text_len: usize) -> Self {
+        TextLayoutCache {
+            layouts: LruCache::new(max_layouts, max_text_len),
+        }
+    }
+
+    /// Return the cached layout matching the builder or build and cache it.
+    pub fn get_or_build(&mut self, builder: TextLayoutBuilder) -> Result<Rc<TextLayout>, Error> {
+        let key = match builder.cache_key() {
+            Some(key) => key,
+            None => return Ok(Rc::new(piet::TextLayoutBuilder::build(builder)?)),
+        };
+        if let Some(layout) = self.layouts.get(&key) {
+            return Ok(layout.clone());
+        }
+
+        let layout = Rc::new(piet::TextLayoutBuilder::build(builder)?);
+        let cost = key.text.len();
+        self.layouts.insert(key, layout.clone(), cost);
+        Ok(layout)
+    }
+
+    pub fn set_limits(&mut self, max_layouts: usize, max_text_len: usize) {
+        self.layouts.set_limits(max_layouts, max_text_len);
+    }
+
+    pub fn clear(&mut self) {
+        self.layouts.clear();
+    }
+
+    /// Number of cached layouts.
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.layouts.hits()
+    }
+
+    /// Number of lookups which built a new layout, uncacheable layouts are not counted.
+    pub fn misses(&self) -> u64 {
+        self.layouts.misses()
+    }
+
+    pub fn reset_counters(&mut self) {
+        self.layouts.reset_counters();
+    }
+}
+
+/// Draws an inline object at the baseline origin of its box.
+type InlineDraw = Rc<dyn Fn(&mut Frame, Point)>;
+
+/// Index and top-left corner of the inline objects drawn by Direct2D.
+type InlineQueue = Rc<RefCell<Vec<(usize, [f32; 2])>>>;
+
+#[derive(Clone)]
+struct InlineBox {
+    metrics: sys::dwrite::InlineObjectMetrics,
+    draw: InlineDraw,
+}
+
+/// Inline object recording its position when drawn, the callback needs the
+/// frame which is not available while Direct2D draws the layout.
+struct QueuedInlineObject {
+    metrics: sys::dwrite::InlineObjectMetrics,
+    index: usize,
+    queue: InlineQueue,
+}
+
+impl sys::dwrite::CustomInlineObject for QueuedInlineObject {
+    fn metrics(&self) -> sys::dwrite::InlineObjectMetrics {
+        self.metrics
+    }
+
+    fn draw(&self, origin: [f32; 2], _is_sideways: bool, _is_right_to_left: bool) {
+        self.queue.borrow_mut().push((self.index, origin));
+    }
+}
+
+/// Height of the lines in a text layout.
+///
+/// DirectWrite has no notion of spacing between paragraphs, paragraphs
+/// requiring additional spacing need to be laid out separately.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineSpacing {
+    /// Line height and baseline derived from the fonts used in each line.
+    Default,
+    /// All lines have the same height, `baseline` is the distance from the
+    /// top of a line to its baseline.
+    Uniform { height: f64, baseline: f64 },
+    /// Scale the line height and baseline derived from the fonts, requires Windows 10.
+    ///
+    /// A `height` of 1.5 results in lines one and a half times the default height.
+    Proportional { height: f64, baseline: f64 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Trimming {
+    granularity: sys::dwrite::TrimmingGranularity,
+    ellipsis: bool,
+}
+
 pub struct TextLayoutBuilder {
-    text_layout: sys::dwrite::TextLayout,
+    factory: sys::dwrite::Factory,
+    format: sys::dwrite::TextFormat,
+    buffer: TextBuffer,
+    /// Fits the text if `None`, as do the other axes without constraint.
+    max_width: Option<f64>,
+    max_height: Option<f64>,
+    word_wrapping: sys::dwrite::WordWrapping,
+    text_alignment: sys::dwrite::TextAlignment,
+    paragraph_alignment: sys::dwrite::ParagraphAlignment,
+    /// Detected from the text if `None`.
+    reading_direction: Option<sys::dwrite::ReadingDirection>,
+    flow_direction: sys::dwrite::FlowDirection,
+    line_spacing: LineSpacing,
+    measuring_mode: sys::dwrite::MeasuringMode,
+    pixels_per_dip: f64,
+    trimming: Option<Trimming>,
+    font_fallback: Option<sys::dwrite::FontFallback>,
+    attributes: Vec<(Range<usize>, Attribute)>,
+}
+
+impl TextLayoutBuilder {
+    /// Width of the layout box, text wraps or gets trimmed at this width.
+    ///
+    /// Without a maximum width the box is as wide as the formatted text and
+    /// horizontal lines only break at newlines.
+    pub fn max_width(mut self, width: f64) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+
+    /// Height of the layout box, without a maximum height the box is as high as
+    /// the formatted text.
+    pub fn max_height(mut self, height: f64) -> Self {
+        self.max_height = Some(height);
+        self
+    }
+
+    pub fn word_wrapping(mut self, wrapping: sys::dwrite::WordWrapping) -> Self {
+        self.word_wrapping = wrapping;
+        self
+    }
+
+    /// Horizontal alignment of the text inside the layout box.
+    pub fn text_alignment(mut self, alignment: sys::dwrite::TextAlignment) -> Self {
+        self.text_alignment = alignment;
+        self
+    }
+
+    /// Vertical alignment of the paragraphs inside the layout box.
+    pub fn paragraph_alignment(mut self, alignment: sys::dwrite::ParagraphAlignment) -> Self {
+        self.paragraph_alignment = alignment;
+        self
+    }
+
+    /// Base direction of the paragraphs.
+    ///
+    /// Defaults to the direction of the first strong character of the first paragraph,
+    /// left-to-right if it has no strong character. All paragraphs share the direction.
+    /// Right-to-left and left-to-right runs inside a paragraph are ordered by
+    /// the bidi algorithm independent of the base direction.
+    pub fn reading_direction(mut self, direction: sys::dwrite::ReadingDirection) -> Self {
+        self.reading_direction = Some(direction);
+        self
+    }
+
+    /// Direction in which lines are stacked, vertical CJK text uses
+    /// `ReadingDirection::TopToBottom` with `FlowDirection::RightToLeft`.
+    pub fn flow_direction(mut self, direction: sys::dwrite::FlowDirection) -> Self {
+        self.flow_direction = direction;
+        self
+    }
+
+    pub fn line_spacing(mut self, spacing: LineSpacing) -> Self {
+        self.line_spacing = spacing;
+        self
+    }
+
+    /// Measure glyphs like GDI instead of resolution independent, `pixels_per_dip`
+    /// is the DPI scale of the target, e.g. 1.5 at 144 DPI.
+    pub fn measuring_mode(mut self, mode: sys::dwrite::MeasuringMode, pixels_per_dip: f64) -> Self {
+        self.measuring_mode = mode;
+        self.pixels_per_dip = pixels_per_dip;
+        self
+    }
+
+    /// Trim text overflowing the layout box, optionally ending with an ellipsis.
+    pub fn trimming(
+        mut self,
+        granularity: sys::dwrite::TrimmingGranularity,
+        ellipsis: bool,
+    ) -> Self {
+        self.trimming = Some(Trimming {
+            granularity,
+            ellipsis,
+        });
+        self
+    }
+
+    /// Fallback for characters missing in the fonts of the layout, overrides
+    /// the fallback set with `Text::set_font_fallback`.
+    pub fn font_fallback(mut self, fallback: &sys::dwrite::FontFallback) -> Self {
+        self.font_fallback = Some(fallback.clone());
+        self
+    }
+
+    /// Font family of the byte range.
+    pub fn font_family(mut self, range: Range<usize>, family: &str) -> Self {
+        self.attributes.push((range, Attribute::FontFamily(family.to_owned())));
+        self
+    }
+
+    /// Font size of the byte range.
+    pub fn font_size(mut self, range: Range<usize>, size: f64) -> Self {
+        self.attributes.push((range, Attribute::FontSize(size as _)));
+        self
+    }
+
+    /// Font weight of the byte range.
+    pub fn font_weight(mut self, range: Range<usize>, weight: sys::dwrite::FontWeight) -> Self {
+        self.attributes.push((range, Attribute::FontWeight(weight)));
+        self
+    }
+
+    /// Font style of the byte range.
+    pub fn font_style(mut self, range: Range<usize>, style: sys::dwrite::FontStyle) -> Self {
+        self.attributes.push((range, Attribute::FontStyle(style)));
+        self
+    }
+
+    /// Font stretch of the byte range.
+    pub fn font_stretch(mut self, range: Range<usize>, stretch: sys::dwrite::FontStretch) -> Self {
+        self.attributes.push((range, Attribute::FontStretch(stretch)));
+        self
+    }
+
+    pub fn underline(mut self, range: Range<usize>, underline: bool) -> Self {
+        self.attributes.push((range, Attribute::Underline(underline)));
+        self
+    }
+
+    pub fn strikethrough(mut self, range: Range<usize>, strikethrough: bool) -> Self {
+        self.attributes.push((range, Attribute::Strikethrough(strikethrough)));
+        self
+    }
+
+    /// Brush used for the byte range instead of the brush passed when drawing.
+    pub fn foreground(mut self, range: Range<usize>, brush: &Brush) -> Self {
+        self.attributes.push((range, Attribute::Foreground(brush.clone())));
+        self
+    }
+
+    /// OpenType features of the byte range, e.g. `(FontFeatureTag::TABULAR_FIGURES, 1)`.
+    ///
+    /// The features replace features set earlier for overlapping ranges.
+    pub fn font_features(
+        mut self,
+        range: Range<usize>,
+        features: &[(sys::dwrite::FontFeatureTag, u32)],
+    ) -> Self {
+        self.attributes.push((range, Attribute::FontFeatures(features.to_vec())));
+        self
+    }
+
+    /// Replace the byte range with a box, usually the range is a single
+    /// U+FFFC object replacement character.
+    ///
+    /// `baseline` is the distance from the top of the box to the baseline of
+    /// the line. When the layout is drawn by a `Frame`, `draw` is called with
+    /// the frame and the point where the box meets the baseline on the left.
+    /// Custom renderers receive the same point and the index of the object.
+    pub fn inline_object(
+        mut self,
+        range: Range<usize>,
+        width: f64,
+        height: f64,
+        baseline: f64,
+        draw: impl Fn(&mut Frame, Point) + 'static,
+    ) -> Self {
+        let metrics = sys::dwrite::InlineObjectMetrics {
+            width: width as _,
+            height: height as _,
+            baseline: baseline as _,
+            supports_sideways: false,
+        };
+        let draw = Rc::new(draw);
+        self.attributes.push((range, Attribute::InlineObject(InlineBox { metrics, draw })));
+        self
+    }
+}
+
+impl TextLayoutBuilder {
+    fn cache_key(&self) -> Option<LayoutKey> {
+        let attributes = self
+            .attributes
+            .iter()
+            .map(|(range, attribute)| Some((range.clone(), attribute.cache_key()?)))
+            .collect::<Option<Vec<_>>>()?;
+        let line_spacing = match self.line_spacing {
+            LineSpacing::Default => (0, 0, 0),
+            LineSpacing::Uniform { height, baseline } => (1, height.to_bits(), baseline.to_bits()),
+            LineSpacing::Proportional { height, baseline } => {
+                (2, height.to_bits(), baseline.to_bits())
+            }
+        };
+        Some(LayoutKey {
+            text: self.buffer.as_str().to_owned(),
+            collection: self
+                .format
+                .get_font_collection()
+                .map_or(0, |collection| collection.as_raw() as usize),
+            family: self.format.get_font_family_name(),
+            locale: self.format.get_locale_name(),
+            size: self.format.get_font_size().to_bits(),
+            weight: self.format.get_font_weight(),
+            style: self.format.get_font_style(),
+            stretch: self.format.get_font_stretch(),
+            max_width: self.max_width.map(f64::to_bits),
+            max_height: self.max_height.map(f64::to_bits),
+            word_wrapping: self.word_wrapping,
+            text_alignment: self.text_alignment,
+            paragraph_alignment: self.paragraph_alignment,
+            reading_direction: self.reading_direction,
+            flow_direction: self.flow_direction,
+            line_spacing,
+            measuring_mode: self.measuring_mode,
+            pixels_per_dip: self.pixels_per_dip.to_bits(),
+            trimming: self.trimming,
+            font_fallback: self
+                .font_fallback
+                .as_ref()
+                .map_or(0, |fallback| fallback.as_raw() as usize),
+            attributes,
+        })
+    }
 }
 
 impl piet::TextLayoutBuilder for TextLayoutBuilder {
     type Out = TextLayout;
 
     fn build(self) -> Result<Self::Out, Error> {
-        Ok(TextLayout(self.text_layout))
+        let max_width = self.max_width.map_or(UNCONSTRAINED, |width| width as _);
+        let max_height = self.max_height.map_or(UNCONSTRAINED, |height| height as _);
+        let layout = match self.measuring_mode {
+            sys::dwrite::MeasuringMode::Natural => self.factory.create_text_layout(
+                self.buffer.as_wide(),
+                &self.format,
+                max_width,
+                max_height,
+            )?,
+            mode => self.factory.create_gdi_compatible_text_layout(
+                self.buffer.as_wide(),
+                &self.format,
+                max_width,
+                max_height,
+                self.pixels_per_dip as _,
+                mode == sys::dwrite::MeasuringMode::GdiNatural,
+            )?,
+        };
+        layout.set_word_wrapping(self.word_wrapping)?;
+        layout.set_text_alignment(self.text_alignment)?;
+        layout.set_paragraph_alignment(self.paragraph_alignment)?;
+        let reading_direction = match self.reading_direction {
+            Some(direction) => direction,
+            None => detect_reading_direction(
+                &self.factory,
+                self.buffer.as_wide(),
+                &self.format.get_locale_name(),
+            )?,
+        };
+        layout.set_reading_direction(reading_direction)?;
+        layout.set_flow_direction(self.flow_direction)?;
+        let (method, line_spacing, baseline) = match self.line_spacing {
+            LineSpacing::Default => (sys::dwrite::LineSpacingMethod::Default, 0.0, 0.0),
+            LineSpacing::Uniform { height, baseline } => {
+                (sys::dwrite::LineSpacingMethod::Uniform, height, baseline)
+            }
+            LineSpacing::Proportional { height, baseline } => (
+                sys::dwrite::LineSpacingMethod::Proportional,
+                height,
+                baseline,
+            ),
+        };
+        layout.set_line_spacing(method, line_spacing as _, baseline as _)?;
+        if let Some(trimming) = self.trimming {
+            let sign = if trimming.ellipsis {
+                Some(self.factory.create_ellipsis_trimming_sign(&self.format)?)
+            } else {
+                None
+            };
+            layout.set_trimming(trimming.granularity, sign.as_ref())?;
+        }
+        if let Some(ref fallback) = self.font_fallback {
+            layout.set_font_fallback(fallback)?;
+        }
+        let inline_queue = InlineQueue::default();
+        let mut inline_objects = Vec::new();
+        let mut foregrounds = Vec::new();
+        for (range, attribute) in &self.attributes {
+            let range = self.buffer.utf8_range_to_utf16(range.clone());
+            match *attribute {
+                Attribute::FontFamily(ref family) => layout.set_font_family_name(family, range)?,
+                Attribute::FontSize(size) => layout.set_font_size(size, range)?,
+                Attribute::FontWeight(weight) => layout.set_font_weight(weight, range)?,
+                Attribute::FontStyle(style) => layout.set_font_style(style, range)?,
+                Attribute::FontStretch(stretch) => layout.set_font_stretch(stretch, range)?,
+                Attribute::Underline(underline) => layout.set_underline(underline, range)?,
+                Attribute::Strikethrough(strikethrough) => {
+                    layout.set_strikethrough(strikethrough, range)?
+                }
+                Attribute::Foreground(ref brush) => {
+                    foregrounds.push(brush.clone());
+                    layout.set_drawing_effect(brush, range)?
+                }
+                Attribute::FontFeatures(ref features) => {
+                    let typography = self.factory.create_typography()?;
+                    for &(tag, parameter) in features {
+                        typography.add_font_feature(tag, parameter)?;
+                    }
+                    layout.set_typography(&typography, range)?
+                }
+                Attribute::InlineObject(ref inline) => {
+                    let object = sys::dwrite::InlineObject::new(QueuedInlineObject {
+                        metrics: inline.metrics,
+                        index: inline_objects.len(),
+                        queue: inline_queue.clone(),
+                    });
+                    inline_objects.push(inline.clone());
+                    layout.set_inline_object(&object, range)?
+                }
+            }
+        }
+
+        let layout = TextLayout {
+            layout,
+            buffer: self.buffer,
+            word_wrapping: self.word_wrapping,
+            is_vertical: matches!(
+                reading_direction,
+                sys::dwrite::ReadingDirection::TopToBottom
+                    | sys::dwrite::ReadingDirection::BottomToTop
+            ),
+            is_unconstrained: [self.max_width.is_none(), self.max_height.is_none()],
+            foregrounds,
+            inline_objects,
+            inline_queue,
+        };
+        layout.fit_unconstrained()?;
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Text, TextRenderer};
+    use crate::{sys, Brush, TextBuffer};
+    use kurbo::{Point, Vec2};
+    use piet::{FontBuilder as _, Text as _, TextLayoutBuilder as _};
+
+    #[test]
+    fn mixed_direction_hit_testing() {
+        let mut text = Text::new(sys::dwrite::Factory::new());
+        let font = text.new_font_by_name("Segoe UI", 16.0).unwrap().build().unwrap();
+        // Left-to-right paragraph ending with a right-to-left run, followed by a
+        // paragraph starting with a right-to-left run.
+        let source = "abc שלום\nשלום abc";
+        let layout = text
+            .new_text_layout(&font, source)
+            .unwrap()
+            .max_width(400.0)
+            .build()
+            .unwrap();
+        let word = "שלום".len();
+        let second = source.find('\n').unwrap() + 1;
+        assert_eq!(layout.line_count(), 2);
+
+        // Both carets at the run boundary are on the line, in front of the
+        // right-to-left run the caret is at its right edge.
+        let space = layout.hit_test_text_position_trailing(3);
+        let run_start = layout.hit_test_text_position(4);
+        let run_end = layout.hit_test_text_position_trailing(4 + word - 2);
+        assert!(!space.is_right_to_left);
+        assert!(run_start.is_right_to_left);
+        assert!(run_start.point.x > space.point.x + 10.0);
+        assert!((run_end.point.x - space.point.x).abs() < 0.5);
+
+        // The second paragraph shares the left-to-right direction of the first one,
+        // its leading run is reordered inside the paragraph.
+        let first = layout.hit_test_text_position(0);
+        let second_start = layout.hit_test_text_position(second);
+        assert!(first.point.x < 1.0);
+        assert!(second_start.is_right_to_left);
+        assert!(second_start.point.y > first.point.y);
+        assert!(second_start.point.x > 10.0);
+        assert!(second_start.point.x < 100.0);
+
+        let point = second_start.point + Vec2::new(-1.0, second_start.height / 2.0);
+        let hit = layout.hit_test_point(point);
+        assert_eq!(hit.index, second);
+        assert!(hit.is_right_to_left);
+        assert!(!hit.is_trailing_hit);
+    }
+
+    #[test]
+    fn reading_direction() {
+        use sys::dwrite::ReadingDirection::{LeftToRight, RightToLeft};
+
+        let factory = sys::dwrite::Factory::new();
+        let direction = |text: &str| {
+            let buffer = TextBuffer::new(text);
+            super::detect_reading_direction(&factory, buffer.as_wide(), "en-us").unwrap()
+        };
+        assert_eq!(direction("hewwo שלום"), LeftToRight);
+        assert_eq!(direction("שלום hewwo"), RightToLeft);
+        // Digits and punctuation are not strong characters.
+        assert_eq!(direction("12, مرحبا"), RightToLeft);
+        assert_eq!(direction("(1) 世界"), LeftToRight);
+        // Explicit marks are strong characters.
+        assert_eq!(direction("\u{200F}hewwo"), RightToLeft);
+        assert_eq!(direction("\u{200E}שלום"), LeftToRight);
+        // Isolated text is skipped.
+        assert_eq!(direction("\u{2067}hewwo\u{2069} שלום"), RightToLeft);
+        // Without strong characters the first paragraph is left-to-right, later
+        // paragraphs don't affect the direction.
+        assert_eq!(direction(""), LeftToRight);
+        assert_eq!(direction("12, ..."), LeftToRight);
+        assert_eq!(direction("\u{2068}\u{2066}a\u{2069}b\u{2069}"), LeftToRight);
+        assert_eq!(direction("12\nשלום"), LeftToRight);
+        assert_eq!(direction("שלום\nhewwo"), RightToLeft);
+    }
+
+    #[test]
+    fn trimming() {
+        let mut text = Text::new(sys::dwrite::Factory::new());
+        let font = text.new_font_by_name("Segoe UI", 16.0).unwrap().build().unwrap();
+        let source = "hewwo fehui hewwo\nfehui";
+        let build = |text: &mut Text, trimming| {
+            let builder = text
+                .new_text_layout(&font, source)
+                .unwrap()
+                .max_width(60.0)
+                .word_wrapping(sys::dwrite::WordWrapping::NoWrap);
+            let builder = if trimming {
+                builder.trimming(sys::dwrite::TrimmingGranularity::Character, true)
+            } else {
+                builder
+            };
+            builder.build().unwrap()
+        };
+        let untrimmed = build(&mut text, false);
+        let trimmed = build(&mut text, true);
+        assert!(untrimmed.metrics().width > 60.0);
+        assert!(untrimmed.line_metrics().iter().all(|line| !line.is_trimmed));
+
+        // The long line ends with an ellipsis inside the layout box, the short one is kept.
+        let lines = trimmed.line_metrics();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].is_trimmed);
+        assert!(!lines[1].is_trimmed);
+        assert!(trimmed.metrics().width <= 60.0);
+        assert!(trimmed.overhang_metrics().right < 1.0);
+    }
+
+    #[test]
+    fn overhangs() {
+        let mut text = Text::new(sys::dwrite::Factory::new());
+        let font = text.new_font_by_name("Segoe UI", 16.0).unwrap().build().unwrap();
+        let layout = text
+            .new_text_layout(&font, "hewwo fehui\nhewwo")
+            .unwrap()
+            .max_width(40.0)
+            .max_height(10.0)
+            .word_wrapping(sys::dwrite::WordWrapping::NoWrap)
+            .build()
+            .unwrap();
+        let metrics = layout.metrics();
+        let overhang = layout.overhang_metrics();
+
+        // The glyphs of the longest line extend about as far as its advances beyond
+        // the box, both lines extend below it.
+        assert!((overhang.right - (metrics.width - 40.0)).abs() < 4.0);
+        assert!(overhang.bottom > metrics.height - 10.0 - 8.0);
+        assert!(overhang.left < 2.0);
+        assert!(overhang.top < 0.0);
+    }
+
+    #[test]
+    fn paragraph_alignment() {
+        use sys::dwrite::ParagraphAlignment;
+
+        let mut text = Text::new(sys::dwrite::Factory::new());
+        let font = text.new_font_by_name("Segoe UI", 16.0).unwrap().build().unwrap();
+        let source = "hewwo\nfehui";
+        let second = source.find('\n').unwrap() + 1;
+        for &(alignment, offset) in &[
+            (ParagraphAlignment::Near, 0.0),
+            (ParagraphAlignment::Center, 0.5),
+            (ParagraphAlignment::Far, 1.0),
+        ] {
+            let layout = text
+                .new_text_layout(&font, source)
+                .unwrap()
+                .max_height(100.0)
+                .paragraph_alignment(alignment)
+                .build()
+                .unwrap();
+            let lines = layout.line_metrics();
+            let top = offset * (100.0 - layout.height());
+
+            // The paragraphs are aligned as a whole and stay stacked.
+            assert!((layout.metrics().top as f64 - top).abs() < 0.01);
+            assert!((layout.hit_test_text_position(0).point.y - top).abs() < 0.01);
+            let y = layout.hit_test_text_position(second).point.y;
+            assert!((y - top - lines[0].height).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn unconstrained_alignment() {
+        use sys::dwrite::{FlowDirection, ParagraphAlignment, TextAlignment};
+
+        let mut text = Text::new(sys::dwrite::Factory::new());
+        let font = text.new_font_by_name("Segoe UI", 16.0).unwrap().build().unwrap();
+        let source = "hewwo\nfehui hewwo fehui";
+        let mut layout = text
+            .new_text_layout(&font, source)
+            .unwrap()
+            .text_alignment(TextAlignment::Trailing)
+            .paragraph_alignment(ParagraphAlignment::Far)
+            .flow_direction(FlowDirection::BottomToTop)
+            .build()
+            .unwrap();
+
+        // The box fits the text, the short line is aligned to the end of the long one.
+        let metrics = layout.metrics();
+        assert!(metrics.left.abs() < 0.01);
+        assert!(metrics.top.abs() < 0.01);
+        assert!((metrics.layout_width - metrics.width).abs() < 0.01);
+        assert!((metrics.layout_height - metrics.height).abs() < 0.01);
+        assert_eq!(layout.line_count(), 2);
+        let short = layout.hit_test_text_position(0).point.x;
+        assert!(short > 10.0 && short < metrics.width as f64);
+
+        // Constraining the width wraps the lines, the height still fits the text.
+        layout.set_max_width(60.0).unwrap();
+        let metrics = layout.metrics();
+        assert!(layout.line_count() > 2);
+        assert!((metrics.layout_width - 60.0).abs() < 0.01);
+        assert!(metrics.top.abs() < 0.01);
+        assert!((metrics.layout_height - metrics.height).abs() < 0.01);
+    }
+
+    /// Records the inline objects passed to the renderer.
+    #[derive(Default)]
+    struct InlineRecorder {
+        objects: Vec<(usize, Point)>,
+    }
+
+    impl TextRenderer for InlineRecorder {
+        fn draw_glyph_run(
+            &mut self,
+            _baseline_origin: Point,
+            _measuring_mode: sys::dwrite::MeasuringMode,
+            _glyph_run: &sys::dwrite::GlyphRun,
+            _description: &sys::dwrite::GlyphRunDescription,
+            _foreground: Option<&Brush>,
+        ) {
+        }
+
+        fn draw_inline_object(&mut self, baseline_origin: Point, index: usize) {
+            self.objects.push((index, baseline_origin));
+        }
+    }
+
+    #[test]
+    fn inline_object_positions() {
+        let mut text = Text::new(sys::dwrite::Factory::new());
+        let font = text.new_font_by_name("Segoe UI", 16.0).unwrap().build().unwrap();
+        let source = "a\u{FFFC}\n\u{FFFC}b";
+        let first = source.find('\u{FFFC}').unwrap();
+        let second = source.rfind('\u{FFFC}').unwrap();
+        let layout = text
+            .new_text_layout(&font, source)
+            .unwrap()
+            .inline_object(first..first + 3, 20.0, 10.0, 8.0, |_, _| {})
+            .inline_object(second..second + 3, 20.0, 10.0, 8.0, |_, _| {})
+            .build()
+            .unwrap();
+        let lines = layout.line_metrics();
+        let origin = Point::new(5.0, 7.0);
+        let expected = [
+            (0, layout.hit_test_text_position(first).point.x, lines[0].baseline),
+            (1, 0.0, lines[0].height + lines[1].baseline),
+        ];
+
+        // Outlines drain the queue, every draw passes each object once.
+        layout.outline().unwrap();
+        for _ in 0..2 {
+            let mut recorder = InlineRecorder::default();
+            layout.draw(origin, &mut recorder).unwrap();
+            assert_eq!(recorder.objects.len(), expected.len());
+            for (&(index, point), &(expected_index, x, y)) in
+                recorder.objects.iter().zip(&expected)
+            {
+                assert_eq!(index, expected_index);
+                assert!((point.x - origin.x - x).abs() < 0.01);
+                assert!((point.y - origin.y - y).abs() < 0.01);
+            }
+        }
     }
 }
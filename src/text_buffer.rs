@@ -0,0 +1,291 @@
+//! Text storage mapping between Rust's UTF-8 byte offsets and the UTF-16
+//! code unit offsets used by DirectWrite.
+
+use std::ops::Range;
+use unicode_segmentation::GraphemeCursor;
+
+/// Position of a non-ASCII char in both encodings.
+///
+/// ASCII chars occupy a single unit in both encodings, so only the
+/// non-ASCII chars are required to translate between them.
+#[derive(Copy, Clone, Debug)]
+struct WideChar {
+    utf8: usize,
+    utf16: u32,
+    len_utf8: u8,
+    len_utf16: u8,
+}
+
+/// Text in UTF-8 and UTF-16 encoding.
+///
+/// Offsets not on a char boundary are rounded down to the start of the char,
+/// this includes UTF-16 offsets pointing between the two halves of a surrogate pair.
+#[derive(Clone, Debug)]
+pub struct TextBuffer {
+    text: String,
+    wide: Vec<u16>,
+    wide_chars: Vec<WideChar>,
+}
+
+impl TextBuffer {
+    pub fn new(text: &str) -> Self {
+        let mut wide = Vec::with_capacity(text.len());
+        let mut wide_chars = Vec::new();
+        for (utf8, c) in text.char_indices() {
+            if !c.is_ascii() {
+                wide_chars.push(WideChar {
+                    utf8,
+                    utf16: wide.len() as _,
+                    len_utf8: c.len_utf8() as _,
+                    len_utf16: c.len_utf16() as _,
+                });
+            }
+
+            let mut units = [0; 2];
+            wide.extend_from_slice(c.encode_utf16(&mut units));
+        }
+
+        TextBuffer {
+            text: text.to_owned(),
+            wide,
+            wide_chars,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// UTF-16 encoded text, without a terminating NUL.
+    pub fn as_wide(&self) -> &[u16] {
+        &self.wide
+    }
+
+    /// Length of the text in bytes.
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    /// Length of the text in UTF-16 code units.
+    pub fn len_utf16(&self) -> u32 {
+        self.wide.len() as _
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Convert a byte offset into a UTF-16 offset, offsets past the end are clamped.
+    pub fn utf8_to_utf16(&self, index: usize) -> u32 {
+        let index = index.min(self.text.len());
+        // Last non-ASCII char starting at or before the offset.
+        let i = match self.wide_chars.binary_search_by_key(&index, |c| c.utf8) {
+            Ok(i) => return self.wide_chars[i].utf16,
+            Err(0) => return index as _,
+            Err(i) => i - 1,
+        };
+
+        let c = self.wide_chars[i];
+        let end = c.utf8 + c.len_utf8 as usize;
+        if index < end {
+            c.utf16
+        } else {
+            c.utf16 + c.len_utf16 as u32 + (index - end) as u32
+        }
+    }
+
+    /// Convert a UTF-16 offset into a byte offset, offsets past the end are clamped.
+    pub fn utf16_to_utf8(&self, index: u32) -> usize {
+        let index = index.min(self.len_utf16());
+        let i = match self.wide_chars.binary_search_by_key(&index, |c| c.utf16) {
+            Ok(i) => return self.wide_chars[i].utf8,
+            Err(0) => return index as _,
+            Err(i) => i - 1,
+        };
+
+        let c = self.wide_chars[i];
+        let end = c.utf16 + c.len_utf16 as u32;
+        if index < end {
+            c.utf8
+        } else {
+            c.utf8 + c.len_utf8 as usize + (index - end) as usize
+        }
+    }
+
+    pub fn utf8_range_to_utf16(&self, range: Range<usize>) -> Range<u32> {
+        let start = self.utf8_to_utf16(range.start);
+        let end = self.utf8_to_utf16(range.end.max(range.start));
+        start..end
+    }
+
+    pub fn utf16_range_to_utf8(&self, range: Range<u32>) -> Range<usize> {
+        let start = self.utf16_to_utf8(range.start);
+        let end = self.utf16_to_utf8(range.end.max(range.start));
+        start..end
+    }
+
+    pub fn is_grapheme_boundary(&self, index: usize) -> bool {
+        if index > self.text.len() || !self.text.is_char_boundary(index) {
+            return false;
+        }
+        GraphemeCursor::new(index, self.text.len(), true)
+            .is_boundary(&self.text, 0)
+            .unwrap_or(false)
+    }
+
+    /// Closest grapheme boundary before the byte offset.
+    pub fn prev_grapheme_boundary(&self, index: usize) -> Option<usize> {
+        let index = self.floor_char_boundary(index);
+        GraphemeCursor::new(index, self.text.len(), true)
+            .prev_boundary(&self.text, 0)
+            .unwrap_or(None)
+    }
+
+    /// Closest grapheme boundary after the byte offset.
+    pub fn next_grapheme_boundary(&self, index: usize) -> Option<usize> {
+        let index = self.floor_char_boundary(index);
+        GraphemeCursor::new(index, self.text.len(), true)
+            .next_boundary(&self.text, 0)
+            .unwrap_or(None)
+    }
+
+    fn floor_char_boundary(&self, index: usize) -> usize {
+        let mut index = index.min(self.text.len());
+        while !self.text.is_char_boundary(index) {
+            index -= 1;
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextBuffer;
+
+    #[test]
+    fn empty() {
+        let buffer = TextBuffer::new("");
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.len_utf16(), 0);
+        assert_eq!(buffer.utf8_to_utf16(0), 0);
+        assert_eq!(buffer.utf16_to_utf8(0), 0);
+        assert_eq!(buffer.utf8_to_utf16(5), 0);
+        assert_eq!(buffer.next_grapheme_boundary(0), None);
+        assert_eq!(buffer.prev_grapheme_boundary(0), None);
+    }
+
+    #[test]
+    fn ascii() {
+        let buffer = TextBuffer::new("hewwo");
+        assert_eq!(
+            buffer.as_wide(),
+            &"hewwo".encode_utf16().collect::<Vec<_>>()[..]
+        );
+        for i in 0..=5 {
+            assert_eq!(buffer.utf8_to_utf16(i), i as u32);
+            assert_eq!(buffer.utf16_to_utf8(i as u32), i);
+        }
+        assert_eq!(buffer.utf8_to_utf16(10), 5);
+        assert_eq!(buffer.utf16_to_utf8(10), 5);
+    }
+
+    #[test]
+    fn multi_byte() {
+        // 'ä' is 2 bytes, '€' is 3 bytes, both single UTF-16 units.
+        let buffer = TextBuffer::new("aä€b");
+        assert_eq!(buffer.len(), 7);
+        assert_eq!(buffer.len_utf16(), 4);
+
+        let pairs = [(0, 0), (1, 1), (3, 2), (6, 3), (7, 4)];
+        for &(utf8, utf16) in &pairs {
+            assert_eq!(buffer.utf8_to_utf16(utf8), utf16);
+            assert_eq!(buffer.utf16_to_utf8(utf16), utf8);
+        }
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        // '👌' is 4 bytes and a surrogate pair in UTF-16.
+        let buffer = TextBuffer::new("a👌b👌");
+        assert_eq!(buffer.len(), 10);
+        assert_eq!(buffer.len_utf16(), 6);
+
+        let pairs = [(0, 0), (1, 1), (5, 3), (6, 4), (10, 6)];
+        for &(utf8, utf16) in &pairs {
+            assert_eq!(buffer.utf8_to_utf16(utf8), utf16);
+            assert_eq!(buffer.utf16_to_utf8(utf16), utf8);
+        }
+
+        // Between the two halves of the surrogate pair.
+        assert_eq!(buffer.utf16_to_utf8(2), 1);
+        assert_eq!(buffer.utf16_to_utf8(5), 6);
+    }
+
+    #[test]
+    fn inside_char_rounds_down() {
+        let buffer = TextBuffer::new("a€👌");
+        assert_eq!(buffer.utf8_to_utf16(2), 1);
+        assert_eq!(buffer.utf8_to_utf16(3), 1);
+        assert_eq!(buffer.utf8_to_utf16(4), 2);
+        assert_eq!(buffer.utf8_to_utf16(6), 2);
+        assert_eq!(buffer.utf8_to_utf16(8), 4);
+    }
+
+    #[test]
+    fn ranges() {
+        let buffer = TextBuffer::new("hewwo fehui 👌!");
+        assert_eq!(buffer.utf8_range_to_utf16(0..5), 0..5);
+        assert_eq!(buffer.utf8_range_to_utf16(12..17), 12..15);
+        assert_eq!(buffer.utf16_range_to_utf8(12..15), 12..17);
+        assert_eq!(buffer.utf16_range_to_utf8(12..14), 12..16);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let text = "Grüße, 世界! 👨‍👩‍👧 ok";
+        let buffer = TextBuffer::new(text);
+        assert_eq!(
+            buffer.as_wide(),
+            &text.encode_utf16().collect::<Vec<_>>()[..]
+        );
+        for (i, _) in text.char_indices() {
+            assert_eq!(buffer.utf16_to_utf8(buffer.utf8_to_utf16(i)), i);
+            assert_eq!(
+                buffer.utf8_to_utf16(i),
+                text[..i].encode_utf16().count() as u32
+            );
+        }
+    }
+
+    #[test]
+    fn graphemes() {
+        // 'e' followed by a combining acute accent forms a single grapheme.
+        let buffer = TextBuffer::new("ae\u{301}b");
+        assert!(buffer.is_grapheme_boundary(0));
+        assert!(buffer.is_grapheme_boundary(1));
+        assert!(!buffer.is_grapheme_boundary(2));
+        assert!(!buffer.is_grapheme_boundary(3));
+        assert!(buffer.is_grapheme_boundary(4));
+        assert!(buffer.is_grapheme_boundary(5));
+        assert!(!buffer.is_grapheme_boundary(6));
+
+        assert_eq!(buffer.next_grapheme_boundary(1), Some(4));
+        assert_eq!(buffer.next_grapheme_boundary(2), Some(4));
+        assert_eq!(buffer.prev_grapheme_boundary(4), Some(1));
+        assert_eq!(buffer.prev_grapheme_boundary(5), Some(4));
+        assert_eq!(buffer.next_grapheme_boundary(5), None);
+    }
+
+    #[test]
+    fn emoji_graphemes() {
+        // Family emoji joined with zero width joiners.
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let text = format!("a{}b", family);
+        let buffer = TextBuffer::new(&text);
+        let end = 1 + family.len();
+        assert_eq!(buffer.next_grapheme_boundary(1), Some(end));
+        assert_eq!(buffer.prev_grapheme_boundary(end), Some(1));
+        assert!(!buffer.is_grapheme_boundary(5));
+        assert_eq!(buffer.utf8_range_to_utf16(1..end), 1..9);
+    }
+}